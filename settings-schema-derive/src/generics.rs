@@ -0,0 +1,207 @@
+use crate::TokenStream2;
+use proc_macro2::{Group, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{ext::IdentExt, GenericParam, Generics, Ident, Member, Type, Visibility};
+
+fn mentions_ident(tokens: TokenStream2, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(token_ident) => token_ident == *ident,
+        TokenTree::Group(group) => mentions_ident(group.stream(), ident),
+        _ => false,
+    })
+}
+
+fn replace_self_tokens(tokens: TokenStream2, self_ts: &TokenStream2) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .flat_map(|token| match token {
+            TokenTree::Ident(ident) if ident == "Self" => self_ts.clone(),
+            TokenTree::Group(group) => {
+                let mut new_group = Group::new(
+                    group.delimiter(),
+                    replace_self_tokens(group.stream(), self_ts),
+                );
+                new_group.set_span(group.span());
+                TokenTree::Group(new_group).into()
+            }
+            token => token.into(),
+        })
+        .collect()
+}
+
+// Replace `Self` in a field type with the derived type. Inside the generated default
+// representation `Self` would refer to the default representation itself
+pub fn replace_self(ty: &Type, self_ts: &TokenStream2) -> Type {
+    if mentions_self(ty.to_token_stream()) {
+        syn::parse2(replace_self_tokens(ty.to_token_stream(), self_ts)).unwrap()
+    } else {
+        ty.clone()
+    }
+}
+
+fn mentions_self(tokens: TokenStream2) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "Self",
+        TokenTree::Group(group) => mentions_self(group.stream()),
+        _ => false,
+    })
+}
+
+// Type and const parameters of the derived type that are used by a default representation struct.
+// The default representation of a type parameter `T` is `<T as SettingsSchema>::Default`, so type
+// parameters are bound by the `SettingsSchema` trait.
+pub struct DefaultGenerics {
+    params_ts: Vec<TokenStream2>,
+    args: Vec<Ident>,
+}
+
+impl DefaultGenerics {
+    // `types` are the types of the fields contained in the default representation. Lifetime
+    // parameters are ignored.
+    pub fn new<'a>(generics: &Generics, types: impl IntoIterator<Item = &'a Type>) -> Self {
+        let types_ts = types
+            .into_iter()
+            .map(ToTokens::to_token_stream)
+            .collect::<TokenStream2>();
+
+        let mut params_ts = vec![];
+        let mut args = vec![];
+        for param in &generics.params {
+            match param {
+                GenericParam::Type(param) if mentions_ident(types_ts.clone(), &param.ident) => {
+                    let ident = &param.ident;
+                    params_ts.push(quote!(#ident: settings_schema::SettingsSchema));
+                    args.push(ident.clone());
+                }
+                GenericParam::Const(param) if mentions_ident(types_ts.clone(), &param.ident) => {
+                    let ident = &param.ident;
+                    let ty = &param.ty;
+                    params_ts.push(quote!(const #ident: #ty));
+                    args.push(ident.clone());
+                }
+                _ => (),
+            }
+        }
+
+        Self { params_ts, args }
+    }
+
+    // Type parameters that must implement `SettingsSchema`
+    pub fn type_params<'a>(&'a self, generics: &'a Generics) -> impl Iterator<Item = &'a Ident> {
+        generics
+            .type_params()
+            .map(|param| &param.ident)
+            .filter(move |ident| self.args.contains(ident))
+    }
+
+    // Parameters with bounds, for declarations and impl blocks
+    pub fn params_ts(&self) -> TokenStream2 {
+        if self.params_ts.is_empty() {
+            quote!()
+        } else {
+            let params_ts = &self.params_ts;
+            quote!(<#(#params_ts),*>)
+        }
+    }
+
+    // Type and const parameters of the derived type that are not used by the default
+    // representation, without their default values. Lifetime parameters are not supported
+    pub fn unused_params(&self, generics: &Generics) -> Vec<GenericParam> {
+        let mut params = vec![];
+        for param in &generics.params {
+            match param {
+                GenericParam::Type(param) if !self.args.contains(&param.ident) => {
+                    let mut param = param.clone();
+                    param.eq_token = None;
+                    param.default = None;
+                    params.push(GenericParam::Type(param));
+                }
+                GenericParam::Const(param) if !self.args.contains(&param.ident) => {
+                    let mut param = param.clone();
+                    param.eq_token = None;
+                    param.default = None;
+                    params.push(GenericParam::Const(param));
+                }
+                _ => (),
+            }
+        }
+
+        params
+    }
+
+    // Names of the used arguments, for the schema name of the derived type. The other parameters
+    // don't affect the schema
+    pub fn arg_names_ts(&self, generics: &Generics) -> Vec<TokenStream2> {
+        generics
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                GenericParam::Type(param) if self.args.contains(&param.ident) => {
+                    let ident = &param.ident;
+                    Some(quote!(settings_schema::schema_name_of::<#ident>()))
+                }
+                GenericParam::Const(param) if self.args.contains(&param.ident) => {
+                    let ident = &param.ident;
+                    Some(quote!(#ident.to_string()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn args_ts(&self) -> TokenStream2 {
+        if self.args.is_empty() {
+            quote!()
+        } else {
+            let args = &self.args;
+            quote!(<#(#args),*>)
+        }
+    }
+}
+
+// Declaration of a default representation struct. `fields_ts` is the body of the struct, and
+// `members` are the names or indices of its fields. Clone and Debug are implemented manually,
+// because the derive macros would require the type parameters themselves to implement these
+// traits, instead of their default representation.
+pub fn default_struct_ts(
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &DefaultGenerics,
+    fields_ts: &TokenStream2,
+    members: &[Member],
+) -> TokenStream2 {
+    let params_ts = generics.params_ts();
+    let args_ts = generics.args_ts();
+
+    let ident_string = ident.to_string();
+    let debug_ts = if let Some(Member::Unnamed(_)) = members.first() {
+        quote!(f.debug_tuple(#ident_string)#(.field(&self.#members))*.finish())
+    } else {
+        let names = members.iter().map(|member| match member {
+            Member::Named(ident) => ident.unraw().to_string(),
+            Member::Unnamed(index) => index.index.to_string(),
+        });
+        quote!(f.debug_struct(#ident_string)#(.field(#names, &self.#members))*.finish())
+    };
+
+    quote! {
+        #[allow(non_snake_case)]
+        #[derive(settings_schema::Serialize, settings_schema::Deserialize)]
+        #[serde(bound = "")]
+        #vis struct #ident #params_ts #fields_ts
+
+        impl #params_ts Clone for #ident #args_ts {
+            fn clone(&self) -> Self {
+                Self {
+                    #(#members: Clone::clone(&self.#members),)*
+                }
+            }
+        }
+
+        impl #params_ts std::fmt::Debug for #ident #args_ts {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                #debug_ts
+            }
+        }
+    }
+}
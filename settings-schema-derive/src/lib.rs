@@ -1,15 +1,19 @@
+mod generics;
+mod serde_attrs;
 mod ty;
 
 use darling::{ast, FromDeriveInput, FromField, FromMeta, FromVariant};
+use generics::DefaultGenerics;
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
-use quote::{quote, ToTokens};
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use serde_attrs::{RenameRule, SerdeAttrs};
 use std::string::ToString;
 use syn::{
-    punctuated::Punctuated, DeriveInput, Error, Expr, ExprLit, Ident, Lit, Meta, Token, Type,
-    Visibility,
+    ext::IdentExt, parse_quote, punctuated::Punctuated, Attribute, Data, DeriveInput, Error, Expr,
+    ExprLit, Generics, Ident, Index, Lit, Member, Meta, Path, Token, Type, TypePath, Visibility,
 };
-use ty::{NumericGuiType, TypeSchemaData};
+use ty::{DurationUnit, NormalizedRange, NumericGuiType, TypeOverride, TypeSchemaData};
 
 type TResult<T = TokenStream2> = Result<T, TokenStream>;
 
@@ -69,9 +73,65 @@ impl FromMeta for StringMap {
     }
 }
 
+// Join the `#[doc]` attributes (doc comments) the same way rustdoc does: the common indentation is
+// removed, and the leading and trailing whitespace is trimmed.
+fn doc_string(attrs: &[Attribute]) -> Option<String> {
+    let mut lines = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("doc")) {
+        if let Meta::NameValue(name_value) = &attr.meta {
+            if let Expr::Lit(ExprLit {
+                lit: Lit::Str(string),
+                ..
+            }) = &name_value.value
+            {
+                // Empty `///` lines are empty strings, that separate paragraphs
+                lines.extend(string.value().split('\n').map(str::to_owned));
+            }
+        }
+    }
+
+    let indentation = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let doc = lines
+        .iter()
+        .map(|line| line.get(indentation..).unwrap_or("").trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned();
+
+    if doc.is_empty() {
+        None
+    } else {
+        Some(doc)
+    }
+}
+
+// Generate the entry strings. The doc comment is used as `help` string, unless `help` is set
+// explicitly
+fn entry_strings_ts(strings: StringMap, attrs: &[Attribute]) -> Vec<TokenStream2> {
+    let mut strings = strings.0;
+    if !strings.iter().any(|(key, _)| key == "help") {
+        if let Some(doc) = doc_string(attrs) {
+            strings.push(("help".into(), doc));
+        }
+    }
+
+    strings
+        .into_iter()
+        .map(|(key, value)| quote!((#key.into(), #value.into())))
+        .collect()
+}
+
 #[derive(FromField)]
-#[darling(attributes(schema))]
+#[darling(attributes(schema), forward_attrs(doc, serde))]
 struct FieldMeta {
+    attrs: Vec<Attribute>,
+
     vis: Visibility,
 
     ident: Option<Ident>,
@@ -88,6 +148,54 @@ struct FieldMeta {
     gui: Option<NumericGuiType>,
 
     suffix: Option<String>,
+
+    #[darling(default)]
+    unit: Option<DurationUnit>,
+
+    #[darling(default)]
+    range_normalized: Option<NormalizedRange>,
+
+    default: Option<Expr>,
+
+    default_set: Option<bool>,
+
+    default_enabled: Option<bool>,
+
+    default_element: Option<Expr>,
+
+    #[darling(default)]
+    multi_select: bool,
+
+    #[darling(default)]
+    flatten: bool,
+
+    #[darling(default)]
+    skip: bool,
+
+    // Mirror type of a remote type, see `DeriveInputMeta::remote`
+    with: Option<Path>,
+
+    // Description of the leaf type, for types that are not recognized by their name. Set with `as`,
+    // see `rename_as_attrs()`
+    as_: Option<TypeOverride>,
+
+    // Type aliased by the field type. Aliases can't be resolved by the derive macro, so the schema is
+    // generated for this type instead
+    alias_of: Option<Type>,
+
+    // Reference the custom leaf type by name instead of inlining it, to break cycles of mutually
+    // recursive types
+    #[darling(default)]
+    reference: bool,
+
+    // Name of the type being derived, set for every field. Fields of this type, spelled with the
+    // bare name or `Self`, are recursive, and they are referenced by name in the schema
+    #[darling(skip)]
+    recursive_ident: Option<Ident>,
+
+    // Type parameters of the type being derived. They shadow the supported types with the same name
+    #[darling(skip)]
+    type_params: Vec<Ident>,
 }
 
 #[derive(FromMeta)]
@@ -96,11 +204,33 @@ enum ChoiceControlType {
     ButtonGroup,
 }
 
+// The `gui` container attribute is a choice control type for enums and a numeric GUI type for
+// newtype structs
+#[allow(clippy::large_enum_variant)]
+enum ContainerGuiType {
+    Choice(ChoiceControlType),
+    Numeric(NumericGuiType),
+}
+
+impl FromMeta for ContainerGuiType {
+    fn from_meta(item: &Meta) -> darling::Result<Self> {
+        ChoiceControlType::from_meta(item)
+            .map(Self::Choice)
+            .or_else(|_| NumericGuiType::from_meta(item).map(Self::Numeric))
+    }
+}
+
 #[derive(FromVariant)]
-#[darling(attributes(schema), supports(unit, newtype, named))]
+#[darling(
+    attributes(schema),
+    forward_attrs(doc, serde),
+    supports(unit, newtype, tuple, named)
+)]
 struct VariantMeta {
     ident: Ident,
 
+    attrs: Vec<Attribute>,
+
     #[darling(default)]
     strings: StringMap,
 
@@ -110,44 +240,182 @@ struct VariantMeta {
     #[darling(default)]
     collapsible: bool,
 
+    default: Option<Expr>,
+
+    #[darling(default)]
+    default_variant: bool,
+
+    #[darling(default)]
+    skip: bool,
+
     fields: ast::Fields<FieldMeta>,
 }
 
 #[derive(FromDeriveInput)]
-#[darling(attributes(schema), supports(struct_named, enum_any))]
+#[darling(
+    attributes(schema),
+    forward_attrs(doc, serde),
+    supports(struct_named, struct_newtype, struct_tuple, enum_any)
+)]
 struct DeriveInputMeta {
+    attrs: Vec<Attribute>,
+
     data: ast::Data<VariantMeta, FieldMeta>,
 
     #[darling(default)]
-    gui: Option<ChoiceControlType>,
+    gui: Option<ContainerGuiType>,
 
     #[darling(default)]
     collapsible: bool,
+
+    // The following attributes are forwarded to the field of newtype structs
+    suffix: Option<String>,
+
+    #[darling(default)]
+    unit: Option<DurationUnit>,
+
+    #[darling(default)]
+    range_normalized: Option<NormalizedRange>,
+
+    // Path of a type from another crate described by this (mirror) type. Fields of the remote type
+    // select the mirror with the `with` attribute
+    remote: Option<Path>,
+
+    // Every use of the type is a reference to its definition, as if the fields were marked with
+    // `reference`
+    #[darling(default)]
+    reference: bool,
+}
+
+// Name of a field or variant in the serialized settings, taking into account the serde `rename`
+// attribute or the `rename_all` rule of the parent
+fn serialized_name(
+    ident: &Ident,
+    rename: Option<String>,
+    rename_rule: Option<RenameRule>,
+    apply_rule: fn(RenameRule, &str) -> String,
+) -> String {
+    let ident_string = ident.unraw().to_string();
+
+    if let Some(name) = rename {
+        name
+    } else if let Some(rule) = rename_rule {
+        apply_rule(rule, &ident_string)
+    } else {
+        ident_string
+    }
+}
+
+fn field_name(ident: &Ident, rename: Option<String>, rule: Option<RenameRule>) -> String {
+    serialized_name(ident, rename, rule, RenameRule::apply_to_field)
+}
+
+fn variant_name(ident: &Ident, rename: Option<String>, rule: Option<RenameRule>) -> String {
+    serialized_name(ident, rename, rule, RenameRule::apply_to_variant)
+}
+
+// The default representation must be serialized with the same names of the original type, so that
+// the schema default values match the entry names
+fn rename_attr_ts(ident: &Ident, name: &str) -> Option<TokenStream2> {
+    (ident.unraw() != name).then(|| quote!(#[serde(rename = #name)]))
+}
+
+fn entry_names_collision_message(ty_name: &str) -> String {
+    format!(
+        "[SettingsSchema] Entry names of `{}` collide. Check the names of the flattened fields",
+        ty_name
+    )
 }
 
 struct SchemaData {
-    // Fields for the schema representation struct. In case of struct, the fields have the same name
-    // of the original fields. Incase of enum, adds the field `variant` plus the name of the
-    // variants of the original enum
+    // Fields for the schema representation struct, including the delimiters. In case of struct, the
+    // fields have the same name of the original fields. Incase of enum, adds the field `variant`
+    // plus the name of the variants of the original enum
     default_fields_ts: TokenStream2,
 
     // Schema instatiation code, to be inserted into the schema() method
     schema_code_ts: TokenStream2,
 
+    // Conversion code from the default representation to the original type, to be inserted into
+    // the From::from() method
+    from_default_code_ts: TokenStream2,
+
+    // Pattern that destructures a reference to the original struct or variant, binding the
+    // variables used by `from_value_code_ts`. It is not used for enums
+    value_pattern_ts: Option<TokenStream2>,
+
+    // Code that updates the default representation with a value of the original type, to be
+    // inserted into the from_value() method
+    from_value_code_ts: TokenStream2,
+
+    // Instantiation code of the default representation, to be inserted into the Default::default()
+    // method
+    default_code_ts: TokenStream2,
+
     // Auxiliary objects for enums (default variant and default variants data)
     aux_objects_ts: Option<TokenStream2>,
+
+    // Expression of type `EntryNames`, used to check for name collisions caused by flattened
+    // fields. It is not used for enums
+    entry_names_ts: Option<TokenStream2>,
+
+    // Names or indices of the fields of the schema representation struct
+    default_members: Vec<Member>,
+
+    // Code that adds the definitions of the named types referenced by the fields, to be inserted
+    // into the definitions() method
+    definitions_code_ts: TokenStream2,
+}
+
+fn is_field_skipped(meta: &FieldMeta) -> TResult<bool> {
+    Ok(meta.skip || serde_attrs::parse(&meta.attrs)?.skip)
+}
+
+// Types of the fields that are part of the default representation
+fn schema_field_types(meta: &[FieldMeta]) -> TResult<Vec<&Type>> {
+    let mut types = vec![];
+    for meta in meta {
+        if !is_field_skipped(meta)? {
+            types.push(&meta.ty);
+        }
+    }
+
+    Ok(types)
 }
 
+// `ctor_ts` is the path used to instantiate the original struct or variant from its fields, and
+// `default_ctor_ts` is the same for the default representation struct. `rename_rule` is the serde
+// casing transformation of the field names
+// `strings_ts` are the strings of the section itself, empty for struct variants
 fn named_fields_schema(
     collapsible: bool,
+    strings_ts: &[TokenStream2],
     meta: Vec<FieldMeta>,
+    rename_rule: Option<RenameRule>,
     vis_override: Option<Visibility>,
+    ctor_ts: TokenStream2,
+    default_ctor_ts: TokenStream2,
 ) -> TResult<SchemaData> {
     let mut default_entries_ts = vec![];
     let mut schema_entries_ts = vec![];
+    let mut from_default_fields_ts = vec![];
+    let mut value_pattern_fields_ts = vec![];
+    let mut from_value_fields_ts = vec![];
+    let mut default_fields_code_ts = vec![];
+    let mut entry_names = vec![];
+    let mut flattened_entry_names_ts = vec![];
+    let mut default_members = vec![];
+    let mut fields_definitions_code_ts = vec![];
+    let mut skipped_names = vec![];
 
     if collapsible {
+        // `gui_collapsed` is not an entry, but it would collide in the flattened default
+        // representation
+        entry_names.push("gui_collapsed".to_owned());
+        default_members.push(Member::Named(format_ident!("gui_collapsed")));
         default_entries_ts.push(quote!(pub gui_collapsed: bool));
+        from_value_fields_ts.push(quote!(gui_collapsed: default.gui_collapsed));
+        default_fields_code_ts.push(quote!(gui_collapsed: false));
     }
 
     for meta in meta {
@@ -157,51 +425,303 @@ fn named_fields_schema(
             &meta.vis
         };
         let field_ident = meta.ident.as_ref().unwrap().clone();
+        let serde_attrs = serde_attrs::parse(&meta.attrs)?;
+
+        // Skipped fields are not part of the default representation: the type does not need to be
+        // supported. Fields skipped only by the schema are still serialized, so their names are
+        // recorded in the schema
+        if meta.skip || serde_attrs.skip {
+            if !serde_attrs.skip {
+                skipped_names.push(field_name(&field_ident, serde_attrs.rename, rename_rule));
+            }
+            from_default_fields_ts.push(quote!(#field_ident: Default::default()));
+            continue;
+        }
+
+        let type_data = ty::schema(&meta.ty, &meta, meta.default_element.as_ref())?;
+        let default_code_ts =
+            ty::field_default_code(&meta.ty, &meta, meta.default.as_ref(), &type_data);
         let TypeSchemaData {
             default_ty_ts,
             schema_code_ts,
-        } = ty::schema(&meta.ty, &meta)?;
-        let field_string = field_ident.to_string();
-
-        let string_key_values_ts = meta
-            .strings
-            .0
-            .into_iter()
-            .map(|(key, value)| quote!((#key.into(), #value.into())));
-        let string_flags = meta.flags;
+            from_default_code_ts,
+            from_value_code_ts,
+            definitions_code_ts,
+            ..
+        } = type_data;
+        fields_definitions_code_ts.push(definitions_code_ts);
 
-        default_entries_ts.push(quote!(#vis #field_ident: #default_ty_ts));
-        schema_entries_ts.push(quote!(settings_schema::SchemaEntry {
-            name: #field_string.into(),
-            strings: [#(#string_key_values_ts),*].into(),
-            flags: [#(#string_flags.into()),*].into(),
-            content: {
-                let default = default.#field_ident;
-                #schema_code_ts
+        if meta.flatten || serde_attrs.flatten {
+            let ty_path = match &meta.ty {
+                Type::Path(TypePath { qself: None, path })
+                    if path
+                        .segments
+                        .iter()
+                        .all(|segment| segment.arguments.is_none()) =>
+                {
+                    path
+                }
+                _ => {
+                    return error(
+                        "`flatten` is supported only for custom struct types",
+                        &meta.ty,
+                    )
+                }
+            };
+            if !meta.strings.0.is_empty() || !meta.flags.is_empty() {
+                return error(
+                    "`strings` and `flag` are not supported on flattened fields",
+                    &field_ident,
+                );
             }
+
+            // The entry names of remote types are stored in the mirror type
+            let entry_names_path = meta.with.as_ref().unwrap_or(ty_path);
+            flattened_entry_names_ts.push(quote!(&#entry_names_path::ENTRY_NAMES));
+            default_entries_ts.push(quote!(#[serde(flatten)] #vis #field_ident: #default_ty_ts));
+            schema_entries_ts.push(quote! {
+                match {
+                    let default = default.#field_ident;
+                    #schema_code_ts
+                } {
+                    settings_schema::SchemaNode::Section {
+                        entries,
+                        skipped: flattened_skipped,
+                        ..
+                    } => {
+                        skipped.extend(flattened_skipped);
+                        entries
+                    }
+                    _ => unreachable!(),
+                }
+            });
+        } else {
+            let field_string = field_name(&field_ident, serde_attrs.rename, rename_rule);
+            let rename_ts = rename_attr_ts(&field_ident, &field_string);
+
+            let string_key_values_ts = entry_strings_ts(meta.strings, &meta.attrs);
+            let string_flags = meta.flags;
+
+            default_entries_ts.push(quote!(#rename_ts #vis #field_ident: #default_ty_ts));
+            schema_entries_ts.push(quote!(vec![settings_schema::SchemaEntry {
+                name: #field_string.into(),
+                strings: [#(#string_key_values_ts),*].into(),
+                flags: [#(#string_flags.into()),*].into(),
+                content: {
+                    let default = default.#field_ident;
+                    #schema_code_ts
+                }
+            }]));
+            entry_names.push(field_string);
+        }
+        default_members.push(Member::Named(field_ident.clone()));
+        from_default_fields_ts.push(quote!(#field_ident: {
+            let default = default.#field_ident;
+            #from_default_code_ts
+        }));
+
+        let value_ident = format_ident!("value_{}", field_ident.unraw());
+        value_pattern_fields_ts.push(quote!(#field_ident: #value_ident));
+        from_value_fields_ts.push(quote!(#field_ident: {
+            let default = default.#field_ident;
+            let value = #value_ident;
+            #from_value_code_ts
         }));
+        default_fields_code_ts.push(quote!(#field_ident: #default_code_ts));
     }
 
     Ok(SchemaData {
-        default_fields_ts: quote!(#(#default_entries_ts,)*),
-        schema_code_ts: quote!(settings_schema::SchemaNode::Section {
-            entries: vec![#(#schema_entries_ts),*],
-            gui_collapsible: #collapsible,
+        default_fields_ts: quote!({ #(#default_entries_ts,)* }),
+        // Each field contributes a list of entries, with more than one entry for flattened fields.
+        // Flattened fields contribute their skipped names too
+        schema_code_ts: quote!({
+            #[allow(unused_mut)]
+            let mut skipped = vec![#(#skipped_names.into()),*];
+            let entries = vec![#(#schema_entries_ts),*].into_iter().flatten().collect();
+
+            settings_schema::SchemaNode::Section {
+                entries,
+                gui_collapsible: #collapsible,
+                strings: [#(#strings_ts),*].into(),
+                skipped,
+            }
         }),
+        from_default_code_ts: quote!(#ctor_ts { #(#from_default_fields_ts),* }),
+        value_pattern_ts: Some(quote!(#ctor_ts { #(#value_pattern_fields_ts,)* .. })),
+        from_value_code_ts: quote!(#default_ctor_ts { #(#from_value_fields_ts),* }),
+        default_code_ts: quote!(#default_ctor_ts { #(#default_fields_code_ts),* }),
+        aux_objects_ts: None,
+        entry_names_ts: Some(quote!(settings_schema::EntryNames {
+            names: &[#(#entry_names),*],
+            flattened: &[#(#flattened_entry_names_ts),*],
+        })),
+        default_members,
+        definitions_code_ts: quote!(#(#fields_definitions_code_ts)*),
+    })
+}
+
+// Move a leaf attribute of a newtype struct to its field
+fn forward_leaf_attr<T>(
+    container_attr: Option<T>,
+    field_attr: &mut Option<T>,
+    name: &str,
+    field_ty: &Type,
+) -> TResult<()> {
+    if container_attr.is_some() {
+        if field_attr.is_some() {
+            return error(
+                &format!(
+                    "`{}` attribute is set both on the newtype struct and on its field",
+                    name
+                ),
+                field_ty,
+            );
+        }
+        *field_attr = container_attr;
+    }
+
+    Ok(())
+}
+
+// Newtype structs are transparent: the schema is the one of the inner type. Other tuple structs and
+// variants with multiple unnamed fields are represented by a `Tuple` node, with entries named after
+// the field positions. `strings_ts` and `vis_override` are used like for named fields
+fn unnamed_fields_schema(
+    strings_ts: &[TokenStream2],
+    meta: Vec<FieldMeta>,
+    vis_override: Option<Visibility>,
+    ctor_ts: TokenStream2,
+    default_ctor_ts: TokenStream2,
+) -> TResult<SchemaData> {
+    let is_newtype = meta.len() == 1;
+
+    let mut default_entries_ts = vec![];
+    let mut schema_entries_ts = vec![];
+    let mut from_default_fields_ts = vec![];
+    let mut value_pattern_fields_ts = vec![];
+    let mut from_value_fields_ts = vec![];
+    let mut default_fields_code_ts = vec![];
+    let mut default_members = vec![];
+    let mut fields_definitions_code_ts = vec![];
+
+    for (index, meta) in meta.into_iter().enumerate() {
+        let serde_attrs = serde_attrs::parse(&meta.attrs)?;
+        if meta.skip || serde_attrs.skip || meta.flatten || serde_attrs.flatten {
+            return error(
+                "`skip` and `flatten` are not supported for unnamed fields",
+                &meta.ty,
+            );
+        }
+        if is_newtype && (!meta.strings.0.is_empty() || !meta.flags.is_empty()) {
+            return error(
+                "`strings` and `flag` are not supported on newtype struct fields",
+                &meta.ty,
+            );
+        }
+
+        let field_index = Index::from(index);
+        default_members.push(Member::Unnamed(field_index.clone()));
+        let type_data = ty::schema(&meta.ty, &meta, meta.default_element.as_ref())?;
+        let default_code_ts =
+            ty::field_default_code(&meta.ty, &meta, meta.default.as_ref(), &type_data);
+        let TypeSchemaData {
+            default_ty_ts,
+            schema_code_ts,
+            from_default_code_ts,
+            from_value_code_ts,
+            definitions_code_ts,
+            ..
+        } = type_data;
+        fields_definitions_code_ts.push(definitions_code_ts);
+        let vis = if let Some(vis) = &vis_override {
+            vis
+        } else {
+            &meta.vis
+        };
+
+        default_entries_ts.push(quote!(#vis #default_ty_ts));
+
+        let content_ts = quote!({
+            let default = default.#field_index;
+            #schema_code_ts
+        });
+        if is_newtype {
+            schema_entries_ts.push(content_ts);
+        } else {
+            let index_string = index.to_string();
+            let string_key_values_ts = entry_strings_ts(meta.strings, &meta.attrs);
+            let string_flags = meta.flags;
+
+            schema_entries_ts.push(quote!(settings_schema::SchemaEntry {
+                name: #index_string.into(),
+                strings: [#(#string_key_values_ts),*].into(),
+                flags: [#(#string_flags.into()),*].into(),
+                content: #content_ts,
+            }));
+        }
+
+        from_default_fields_ts.push(quote!({
+            let default = default.#field_index;
+            #from_default_code_ts
+        }));
+
+        let value_ident = format_ident!("value_{}", index);
+        value_pattern_fields_ts.push(value_ident.clone());
+        from_value_fields_ts.push(quote!({
+            let default = default.#field_index;
+            let value = #value_ident;
+            #from_value_code_ts
+        }));
+        default_fields_code_ts.push(default_code_ts);
+    }
+
+    let schema_code_ts = if is_newtype {
+        schema_entries_ts.remove(0)
+    } else {
+        quote!(settings_schema::SchemaNode::Tuple {
+            entries: vec![#(#schema_entries_ts),*],
+            strings: [#(#strings_ts),*].into(),
+        })
+    };
+
+    Ok(SchemaData {
+        default_fields_ts: quote!((#(#default_entries_ts),*);),
+        schema_code_ts,
+        from_default_code_ts: quote!(#ctor_ts(#(#from_default_fields_ts),*)),
+        value_pattern_ts: Some(quote!(#ctor_ts(#(#value_pattern_fields_ts),*))),
+        from_value_code_ts: quote!(#default_ctor_ts(#(#from_value_fields_ts),*)),
+        default_code_ts: quote!(#default_ctor_ts(#(#default_fields_code_ts),*)),
         aux_objects_ts: None,
+        entry_names_ts: None,
+        default_members,
+        definitions_code_ts: quote!(#(#fields_definitions_code_ts)*),
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 fn variants_schema(
     gui_type: Option<ChoiceControlType>,
+    strings_ts: &[TokenStream2],
+    container_serde_attrs: SerdeAttrs,
     vis: &Visibility,
     ident: &Ident,
+    ctor_ts: &TokenStream2,
+    generics: &Generics,
     meta: Vec<VariantMeta>,
 ) -> TResult<SchemaData> {
     let mut default_variants_ts = vec![];
     let mut variant_entries_ts = vec![];
+    let mut skipped_variants = vec![];
     let mut variants = vec![];
     let mut aux_variants_structs_ts = vec![];
+    let mut from_default_arms_ts = vec![];
+    let mut from_value_arms_ts = vec![];
+    let mut default_variants_code_ts = vec![];
+    let mut default_members = vec![];
+    let mut variants_definitions_code_ts = vec![];
+
+    let default_ty = suffix_ident(ident, "Default");
+    let default_variant_ty = suffix_ident(ident, "DefaultVariant");
 
     let gui_ts = match gui_type {
         None => quote!(None),
@@ -213,27 +733,105 @@ fn variants_schema(
         }
     };
 
-    for meta in meta {
+    let meta = meta
+        .into_iter()
+        .map(|meta| {
+            let serde_attrs = serde_attrs::parse(&meta.attrs)?;
+            let skip = meta.skip || serde_attrs.skip;
+            Ok((meta, serde_attrs, skip))
+        })
+        .collect::<TResult<Vec<_>>>()?;
+
+    let mut default_variant_metas = meta.iter().filter(|(meta, ..)| meta.default_variant);
+    let default_variant_ident =
+        match (default_variant_metas.next(), default_variant_metas.next()) {
+            (Some((meta, _, true)), None) => {
+                return error(
+                    "A skipped variant cannot be the `default_variant`",
+                    &meta.ident,
+                )
+            }
+            (Some((meta, ..)), None) => &meta.ident,
+            (Some(_), Some((meta, ..))) => {
+                return error(
+                    "Only one variant can be marked as `default_variant`",
+                    &meta.ident,
+                )
+            }
+            (None, _) => {
+                if let Some((meta, ..)) = meta.iter().find(|(_, _, skip)| !skip) {
+                    &meta.ident
+                } else {
+                    return error(
+                        "Enums without (non skipped) variants are not supported",
+                        ident,
+                    );
+                }
+            }
+        }
+        .clone();
+
+    for (meta, variant_serde_attrs, skip) in meta {
+        let variant_ident = meta.ident;
+        let variant_string = variant_name(
+            &variant_ident,
+            variant_serde_attrs.rename,
+            container_serde_attrs.rename_all,
+        );
+
+        // Skipped variants are not part of the default representation. When converting from a
+        // value, the previous default variant is kept. Variants skipped only by the schema are
+        // still serialized, so their names are recorded in the schema
+        if skip {
+            if !variant_serde_attrs.skip {
+                skipped_variants.push(variant_string);
+            }
+            from_value_arms_ts.push(quote!(#ctor_ts::#variant_ident { .. } => ()));
+            continue;
+        }
+
         if meta.collapsible && !meta.fields.style.is_struct() {
             return error(
                 "`collapsible` attribute is not supported for variants with non struct data",
-                meta.ident,
+                variant_ident,
             );
         }
 
-        let variant_ident = meta.ident;
-        let variant_string = variant_ident.to_string();
+        let rename_ts = rename_attr_ts(&variant_ident, &variant_string);
 
-        variants.push(variant_ident.clone());
+        variants.push(quote!(#rename_ts #variant_ident));
+
+        let is_newtype = meta.fields.style.is_tuple() && meta.fields.len() == 1;
+        if let (Some(expr), false) = (&meta.default, is_newtype) {
+            return error(
+                "`default` attribute is supported only for variants with one unnamed field",
+                expr,
+            );
+        }
 
         let entry_content_ts = match meta.fields.style {
-            ast::Style::Tuple => {
-                // darling macro attribute makes sure there is one and only one field
+            ast::Style::Tuple if is_newtype => {
                 let field_meta = &meta.fields.fields[0];
+                let type_data = ty::schema(
+                    &field_meta.ty,
+                    field_meta,
+                    field_meta.default_element.as_ref(),
+                )?;
+                let default_code_ts = ty::field_default_code(
+                    &field_meta.ty,
+                    field_meta,
+                    meta.default.as_ref().or(field_meta.default.as_ref()),
+                    &type_data,
+                );
                 let TypeSchemaData {
                     default_ty_ts,
                     schema_code_ts,
-                } = ty::schema(&field_meta.ty, field_meta)?;
+                    from_default_code_ts,
+                    from_value_code_ts,
+                    definitions_code_ts,
+                    ..
+                } = type_data;
+                variants_definitions_code_ts.push(definitions_code_ts);
 
                 if !field_meta.strings.0.is_empty() {
                     return error(
@@ -242,44 +840,130 @@ fn variants_schema(
                     );
                 }
 
-                default_variants_ts.push(quote!(#vis #variant_ident: #default_ty_ts));
+                default_variants_ts.push(quote!(#rename_ts #vis #variant_ident: #default_ty_ts));
+                default_variants_code_ts.push(quote!(#variant_ident: #default_code_ts));
+                default_members.push(Member::Named(variant_ident.clone()));
+                from_default_arms_ts.push(quote! {
+                    #default_variant_ty::#variant_ident => {
+                        let default = default.#variant_ident;
+                        #ctor_ts::#variant_ident(#from_default_code_ts)
+                    }
+                });
+                from_value_arms_ts.push(quote! {
+                    #ctor_ts::#variant_ident(value) => {
+                        default.variant = #default_variant_ty::#variant_ident;
+                        default.#variant_ident = {
+                            let default = default.#variant_ident;
+                            #from_value_code_ts
+                        };
+                    }
+                });
 
                 quote!(Some({
                     let default = default.#variant_ident;
                     #schema_code_ts
                 }))
             }
-            ast::Style::Struct => {
-                let default_ty_ts =
-                    suffix_ident(&suffix_ident(ident, &variant_ident.to_string()), "Default")
-                        .to_token_stream();
+            // Variants with multiple unnamed fields are represented like tuple structs
+            ast::Style::Struct | ast::Style::Tuple => {
+                let aux_default_ty =
+                    suffix_ident(&suffix_ident(ident, &variant_ident.to_string()), "Default");
+                let aux_generics =
+                    DefaultGenerics::new(generics, schema_field_types(&meta.fields.fields)?);
+                let aux_args_ts = aux_generics.args_ts();
+                let variant_ctor_ts = quote!(#ctor_ts::#variant_ident);
                 let SchemaData {
                     default_fields_ts,
                     schema_code_ts,
+                    from_default_code_ts,
+                    value_pattern_ts,
+                    from_value_code_ts,
+                    default_code_ts,
+                    entry_names_ts,
+                    default_members: aux_default_members,
+                    definitions_code_ts,
                     ..
-                } = named_fields_schema(meta.collapsible, meta.fields.fields, Some(vis.clone()))?;
+                } = if meta.fields.style.is_struct() {
+                    named_fields_schema(
+                        meta.collapsible,
+                        &[],
+                        meta.fields.fields,
+                        variant_serde_attrs
+                            .rename_all
+                            .or(container_serde_attrs.rename_all_fields),
+                        Some(vis.clone()),
+                        variant_ctor_ts,
+                        aux_default_ty.to_token_stream(),
+                    )?
+                } else {
+                    unnamed_fields_schema(
+                        &[],
+                        meta.fields.fields,
+                        Some(vis.clone()),
+                        variant_ctor_ts,
+                        aux_default_ty.to_token_stream(),
+                    )?
+                };
 
-                default_variants_ts.push(quote!(#vis #variant_ident: #default_ty_ts));
-                aux_variants_structs_ts.push(quote! {
-                    #[derive(settings_schema::Serialize, settings_schema::Deserialize, Clone, Debug)]
-                    #vis struct #default_ty_ts {
-                        #default_fields_ts
+                variants_definitions_code_ts.push(definitions_code_ts);
+                default_variants_ts
+                    .push(quote!(#rename_ts #vis #variant_ident: #aux_default_ty #aux_args_ts));
+                default_variants_code_ts.push(quote!(#variant_ident: #default_code_ts));
+                default_members.push(Member::Named(variant_ident.clone()));
+                from_default_arms_ts.push(quote! {
+                    #default_variant_ty::#variant_ident => {
+                        let default = default.#variant_ident;
+                        #from_default_code_ts
+                    }
+                });
+                from_value_arms_ts.push(quote! {
+                    #value_pattern_ts => {
+                        default.variant = #default_variant_ty::#variant_ident;
+                        default.#variant_ident = {
+                            let default = default.#variant_ident;
+                            #from_value_code_ts
+                        };
                     }
                 });
+                let collision_message =
+                    entry_names_collision_message(&format!("{}::{}", ident, variant_ident));
+                let collision_check_ts = entry_names_ts.map(|entry_names_ts| {
+                    quote!(
+                        const _: () =
+                            assert!(!#entry_names_ts.has_duplicates(), #collision_message);
+                    )
+                });
+                let aux_struct_ts = generics::default_struct_ts(
+                    vis,
+                    &aux_default_ty,
+                    &aux_generics,
+                    &default_fields_ts,
+                    &aux_default_members,
+                );
+                aux_variants_structs_ts.push(quote! {
+                    #aux_struct_ts
+
+                    #collision_check_ts
+                });
 
                 quote!(Some({
                     let default = default.#variant_ident;
                     #schema_code_ts
                 }))
             }
-            ast::Style::Unit => quote!(None),
+            ast::Style::Unit => {
+                from_default_arms_ts.push(quote! {
+                    #default_variant_ty::#variant_ident => #ctor_ts::#variant_ident
+                });
+                from_value_arms_ts.push(quote! {
+                    #ctor_ts::#variant_ident => default.variant = #default_variant_ty::#variant_ident
+                });
+
+                quote!(None)
+            }
         };
 
-        let string_key_values_ts = meta
-            .strings
-            .0
-            .into_iter()
-            .map(|(key, value)| quote!((#key.into(), #value.into())));
+        let string_key_values_ts = entry_strings_ts(meta.strings, &meta.attrs);
         let string_flags = meta.flags;
 
         variant_entries_ts.push(quote!(settings_schema::SchemaEntry {
@@ -290,13 +974,11 @@ fn variants_schema(
         }));
     }
 
-    let default_variant_ty = suffix_ident(ident, "DefaultVariant");
-
     Ok(SchemaData {
-        default_fields_ts: quote! {
+        default_fields_ts: quote!({
             #(#default_variants_ts,)*
             #vis variant: #default_variant_ty,
-        },
+        }),
         schema_code_ts: quote!(settings_schema::SchemaNode::Choice {
             default: settings_schema::to_json_value(default.variant)
                 .unwrap()
@@ -304,8 +986,30 @@ fn variants_schema(
                 .unwrap()
                 .into(),
             variants: vec![#(#variant_entries_ts),*],
-            gui: #gui_ts
+            gui: #gui_ts,
+            strings: [#(#strings_ts),*].into(),
+            skipped: vec![#(#skipped_variants.into()),*],
         }),
+        from_default_code_ts: quote! {
+            match default.variant {
+                #(#from_default_arms_ts,)*
+            }
+        },
+        value_pattern_ts: None,
+        default_code_ts: quote! {
+            #default_ty {
+                #(#default_variants_code_ts,)*
+                variant: #default_variant_ty::#default_variant_ident,
+            }
+        },
+        from_value_code_ts: quote! {{
+            let mut default = default;
+            match value {
+                #(#from_value_arms_ts,)*
+            }
+
+            default
+        }},
         aux_objects_ts: Some(quote! {
             #(#aux_variants_structs_ts)*
 
@@ -314,18 +1018,55 @@ fn variants_schema(
                 #(#variants,)*
             }
         }),
+        entry_names_ts: None,
+        default_members: default_members
+            .into_iter()
+            .chain([Member::Named(format_ident!("variant"))])
+            .collect(),
+        definitions_code_ts: quote!(#(#variants_definitions_code_ts)*),
     })
 }
 
+// `as` is a keyword, that is rejected as attribute name when parsing the field attributes. It is
+// renamed to `as_` beforehand
+fn rename_as_attrs(attrs: &mut [Attribute]) {
+    for attr in attrs
+        .iter_mut()
+        .filter(|attr| attr.path().is_ident("schema"))
+    {
+        if let Meta::List(list) = &mut attr.meta {
+            let mut item_start = true;
+            list.tokens = list
+                .tokens
+                .clone()
+                .into_iter()
+                .map(|token| {
+                    let token = match token {
+                        TokenTree::Ident(ident) if item_start && ident == "as" => {
+                            TokenTree::Ident(Ident::new("as_", ident.span()))
+                        }
+                        token => token,
+                    };
+                    item_start =
+                        matches!(&token, TokenTree::Punct(punct) if punct.as_char() == ',');
+
+                    token
+                })
+                .collect();
+        }
+    }
+}
+
 // Generate new code from the given struct or enum.
 //
 // In case of a struct two things are created:
 // * a default settings representation (struct <StructName>Default)
-// * a impl with a schema() method, that returns the schema associated to the current struct
+// * a impl of the `SettingsSchema` trait, with a schema() method that returns the schema
+//   associated to the current struct
 // The default representation is a struct that contains each of the original fields, where the types
 // are substituted with the matching default representation type.
 //
-// Like for structs, for enums the default settings representation and a schema method are generated.
+// Like for structs, for enums the default settings representation and the trait impl are generated.
 // Some auxiliary objects are also generated: the default variant (enum <EnumName>DefaultVariant)
 // and default variants stuctures (struct <EnumName><VariantName>Default). The default variant is a
 // plain old enum with the same variants as the original enum but no variant data. The default
@@ -334,59 +1075,413 @@ fn variants_schema(
 // The default representation struct contains the `variant` field of type default variant; the rest
 // of the fields are the name of the original variants, without casing transformations. Only
 // variants which contains data are inserted as fields in the default representation struct.
-fn schema(derive_input: DeriveInput) -> TResult {
-    if !derive_input.generics.params.is_empty() {
-        return error("Generics not supported", &derive_input.generics);
+// Serde renames of fields and variants are copied to the default representation, so that it is
+// serialized with the same names used by the schema entries.
+// The trait impl also exposes the default representation type and the conversions. Custom field
+// types are resolved through the trait too, so any type implementing it can be used, and the
+// default representation structs are generic over the type parameters used by their fields.
+fn schema(mut derive_input: DeriveInput) -> TResult {
+    if let Some(param) = derive_input.generics.lifetimes().next() {
+        return error("Lifetime parameters are not supported", param);
     }
 
-    let meta: DeriveInputMeta =
+    match &mut derive_input.data {
+        Data::Struct(data) => {
+            for field in &mut data.fields {
+                rename_as_attrs(&mut field.attrs);
+            }
+        }
+        Data::Enum(data) => {
+            for field in data
+                .variants
+                .iter_mut()
+                .flat_map(|variant| &mut variant.fields)
+            {
+                rename_as_attrs(&mut field.attrs);
+            }
+        }
+        Data::Union(_) => (),
+    }
+
+    let mut meta: DeriveInputMeta =
         FromDeriveInput::from_derive_input(&derive_input).map_err(|e| e.write_errors())?;
 
-    if meta.gui.is_some() && meta.data.is_struct() {
+    let fields = match &mut meta.data {
+        ast::Data::Enum(variants) => variants
+            .iter_mut()
+            .flat_map(|variant| variant.fields.fields.iter_mut())
+            .collect::<Vec<_>>(),
+        ast::Data::Struct(fields) => fields.fields.iter_mut().collect(),
+    };
+    let self_ident = &derive_input.ident;
+    let (_, self_generics_ts, _) = derive_input.generics.split_for_impl();
+    let self_ts = quote!(#self_ident #self_generics_ts);
+    let type_params = derive_input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    for field in fields {
+        // The alias is the same type as the aliased one, so the field is treated as if it had the
+        // aliased type
+        if let Some(alias_of) = field.alias_of.take() {
+            field.ty = alias_of;
+        }
+
+        // Fields that contain the type being derived are recursive. Remote types are excluded,
+        // since their fields refer to the remote type instead of the mirror
+        if meta.remote.is_none() {
+            field.ty = generics::replace_self(&field.ty, &self_ts);
+            field.recursive_ident = Some(self_ident.clone());
+        }
+
+        field.type_params = type_params.clone();
+
+        if field.multi_select {
+            ty::check_multi_select(&field.ty, &field.type_params)?;
+        }
+    }
+
+    let is_named_struct =
+        matches!(&meta.data, ast::Data::Struct(fields) if fields.style.is_struct());
+    let is_newtype = matches!(&meta.data, ast::Data::Struct(fields) if fields.style.is_tuple() && fields.len() == 1);
+
+    let mut newtype_gui = None;
+    let gui_type = match meta.gui {
+        Some(ContainerGuiType::Choice(_)) if !meta.data.is_enum() => {
+            return error(
+                "`gui` attribute not supported on structs",
+                derive_input.ident,
+            )
+        }
+        Some(ContainerGuiType::Numeric(_)) if !is_newtype => {
+            return error(
+                "Numeric `gui` attribute is supported only on newtype structs",
+                derive_input.ident,
+            )
+        }
+        Some(ContainerGuiType::Choice(gui_type)) => Some(gui_type),
+        Some(ContainerGuiType::Numeric(gui_type)) => {
+            newtype_gui = Some(gui_type);
+            None
+        }
+        None => None,
+    };
+    if meta.collapsible && !is_named_struct {
         return error(
-            "`gui` attribute not supported on structs",
+            "`collapsible` attribute is supported only on structs with named fields",
             derive_input.ident,
         );
     }
-    if meta.collapsible && meta.data.is_enum() {
+    if !is_newtype
+        && (meta.suffix.is_some() || meta.unit.is_some() || meta.range_normalized.is_some())
+    {
         return error(
-            "`collapsible` attribute not supported on enums",
+            "`suffix`, `unit` and `range_normalized` attributes are supported only on newtype \
+            structs",
             derive_input.ident,
         );
     }
 
-    let gui_type = meta.gui;
+    if meta.reference && meta.remote.is_some() {
+        return error(
+            "`reference` attribute is not supported on remote types",
+            derive_input.ident,
+        );
+    }
+
+    let serde_attrs = serde_attrs::parse(&meta.attrs)?;
     let vis = derive_input.vis;
     let derive_input_ident = derive_input.ident;
     let default_ty_ident = suffix_ident(&derive_input_ident, "Default");
+    // Path used to instantiate and destructure values, which belong to the remote type if any
+    let ctor_ts = if let Some(remote) = &meta.remote {
+        remote.to_token_stream()
+    } else {
+        derive_input_ident.to_token_stream()
+    };
+    let generics = derive_input.generics;
+
+    let default_generics = {
+        let mut types = vec![];
+        match &meta.data {
+            ast::Data::Enum(variants) => {
+                for variant in variants {
+                    if !variant.skip && !serde_attrs::parse(&variant.attrs)?.skip {
+                        types.extend(schema_field_types(&variant.fields.fields)?);
+                    }
+                }
+            }
+            ast::Data::Struct(fields) => types.extend(schema_field_types(&fields.fields)?),
+        }
+
+        DefaultGenerics::new(&generics, types)
+    };
+    let default_params_ts = default_generics.params_ts();
+    let default_args_ts = default_generics.args_ts();
+    let default_ty_ts = quote!(#default_ty_ident #default_args_ts);
+
+    // The type parameters used by the default representation must implement `SettingsSchema`
+    let mut trait_generics = generics.clone();
+    for ident in default_generics.type_params(&generics) {
+        trait_generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#ident: settings_schema::SettingsSchema));
+    }
+    let (impl_generics_ts, ty_generics_ts, where_clause_ts) = trait_generics.split_for_impl();
+
+    // The doc comment of the struct or enum is the help string of its section, tuple or choice node.
+    // Newtype structs are transparent, so they have no node of their own to carry it
+    let strings_ts = entry_strings_ts(StringMap::default(), &meta.attrs);
 
     let SchemaData {
         default_fields_ts,
         schema_code_ts,
+        from_default_code_ts,
+        value_pattern_ts,
+        from_value_code_ts,
+        default_code_ts,
         aux_objects_ts,
+        entry_names_ts,
+        default_members,
+        definitions_code_ts,
     } = match meta.data {
-        ast::Data::Enum(variants) => {
-            variants_schema(gui_type, &vis, &derive_input_ident, variants)?
+        ast::Data::Enum(variants) => variants_schema(
+            gui_type,
+            &strings_ts,
+            serde_attrs,
+            &vis,
+            &derive_input_ident,
+            &ctor_ts,
+            &generics,
+            variants,
+        )?,
+        ast::Data::Struct(ast::Fields {
+            style: ast::Style::Tuple,
+            mut fields,
+            ..
+        }) => {
+            if is_newtype {
+                let field = &mut fields[0];
+                forward_leaf_attr(newtype_gui, &mut field.gui, "gui", &field.ty)?;
+                forward_leaf_attr(meta.suffix, &mut field.suffix, "suffix", &field.ty)?;
+                forward_leaf_attr(meta.unit, &mut field.unit, "unit", &field.ty)?;
+                forward_leaf_attr(
+                    meta.range_normalized,
+                    &mut field.range_normalized,
+                    "range_normalized",
+                    &field.ty,
+                )?;
+            }
+
+            unnamed_fields_schema(
+                &strings_ts,
+                fields,
+                None,
+                ctor_ts.clone(),
+                quote!(#default_ty_ident),
+            )?
         }
-        ast::Data::Struct(ast::Fields { fields, .. }) => {
-            named_fields_schema(meta.collapsible, fields, None)?
+        ast::Data::Struct(ast::Fields { fields, .. }) => named_fields_schema(
+            meta.collapsible,
+            &strings_ts,
+            fields,
+            serde_attrs.rename_all,
+            None,
+            ctor_ts.clone(),
+            quote!(#default_ty_ident),
+        )?,
+    };
+
+    let value_pattern_let_ts = value_pattern_ts.map(|pattern_ts| quote!(let #pattern_ts = value;));
+
+    // Flattened fields refer to the entry names of the struct they contain
+    let entry_names_ts = entry_names_ts.map(|entry_names_ts| {
+        let collision_message = entry_names_collision_message(&derive_input_ident.to_string());
+        quote! {
+            impl #impl_generics_ts #derive_input_ident #ty_generics_ts #where_clause_ts {
+                #[doc(hidden)]
+                pub const ENTRY_NAMES: settings_schema::EntryNames = #entry_names_ts;
+            }
+
+            const _: () = assert!(!#entry_names_ts.has_duplicates(), #collision_message);
+        }
+    });
+
+    let default_struct_ts = generics::default_struct_ts(
+        &vis,
+        &default_ty_ident,
+        &default_generics,
+        &default_fields_ts,
+        &default_members,
+    );
+
+    // Referenced types add their own definition, after the ones of the fields
+    let (schema_fn_ts, definitions_code_ts) = if meta.reference {
+        (
+            quote! {
+                fn schema(default: Self::Default) -> settings_schema::SchemaNode {
+                    settings_schema::referenced_schema::<Self>(default)
+                }
+
+                fn definition_schema(default: Self::Default) -> settings_schema::SchemaNode {
+                    #schema_code_ts
+                }
+            },
+            quote! {
+                #definitions_code_ts
+                settings_schema::add_definition::<Self>(definitions);
+            },
+        )
+    } else {
+        (
+            quote! {
+                fn schema(default: Self::Default) -> settings_schema::SchemaNode {
+                    #schema_code_ts
+                }
+            },
+            definitions_code_ts,
+        )
+    };
+
+    let definitions_fn_ts = if definitions_code_ts.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            fn definitions(definitions: &mut settings_schema::SchemaDefinitions) {
+                #definitions_code_ts
+            }
+        })
+    };
+
+    let trait_items_ts = quote! {
+        #schema_fn_ts
+
+        fn from_default(default: Self::Default) -> #ctor_ts #ty_generics_ts {
+            #from_default_code_ts
+        }
+
+        fn default_from_value(
+            default: Self::Default,
+            value: &#ctor_ts #ty_generics_ts,
+        ) -> Self::Default {
+            #value_pattern_let_ts
+            #from_value_code_ts
+        }
+
+        #definitions_fn_ts
+    };
+
+    // The name is stable across builds, unlike `std::any::type_name()`
+    let schema_name_code_ts = {
+        let path_ts = quote!(concat!(
+            module_path!(),
+            "::",
+            stringify!(#derive_input_ident)
+        ));
+        let arg_names_ts = default_generics.arg_names_ts(&generics);
+        if arg_names_ts.is_empty() {
+            quote!(Some(#path_ts.into()))
+        } else {
+            quote!(Some(format!(
+                "{}<{}>",
+                #path_ts,
+                [#(#arg_names_ts),*].join(", ")
+            )))
+        }
+    };
+
+    // The mirror of a remote type cannot implement `SettingsSchema` (nor `From`) for the remote
+    // type, because of the orphan rules
+    let trait_impl_ts = if meta.remote.is_some() {
+        quote! {
+            impl #impl_generics_ts settings_schema::RemoteSettingsSchema<#ctor_ts #ty_generics_ts>
+                for #derive_input_ident #ty_generics_ts #where_clause_ts
+            {
+                type Default = #default_ty_ts;
+
+                #trait_items_ts
+            }
+        }
+    } else {
+        quote! {
+            impl #impl_generics_ts From<#default_ty_ts> for #derive_input_ident #ty_generics_ts
+                #where_clause_ts
+            {
+                fn from(default: #default_ty_ts) -> Self {
+                    settings_schema::SettingsSchema::from_default(default)
+                }
+            }
+
+            impl #impl_generics_ts settings_schema::SettingsSchema
+                for #derive_input_ident #ty_generics_ts #where_clause_ts
+            {
+                type Default = #default_ty_ts;
+
+                #trait_items_ts
+
+                fn schema_name() -> Option<std::string::String> {
+                    #schema_name_code_ts
+                }
+            }
+        }
+    };
+
+    // The parameters of the derived type that are not used by the default representation are
+    // parameters of the method. The bounds of the parameters are checked through the trait
+    let from_value_ts = {
+        let unused_params = default_generics.unused_params(&generics);
+        let method_params_ts = if unused_params.is_empty() {
+            quote!()
+        } else {
+            quote!(<#(#unused_params),*>)
+        };
+        let (bound_ts, call_ts) = if meta.remote.is_some() {
+            (
+                quote!(settings_schema::RemoteSettingsSchema<#ctor_ts #ty_generics_ts, Default = Self>),
+                quote!(<#derive_input_ident #ty_generics_ts as settings_schema::RemoteSettingsSchema<_>>::default_from_value),
+            )
+        } else {
+            (
+                quote!(settings_schema::SettingsSchema<Default = Self>),
+                quote!(settings_schema::SettingsSchema::default_from_value),
+            )
+        };
+
+        quote! {
+            impl #default_params_ts #default_ty_ts {
+                /// Write the value into a previous default representation (or session), keeping
+                /// the data of the inactive variants and the GUI state.
+                #vis fn from_value #method_params_ts(
+                    default: Self,
+                    value: &#ctor_ts #ty_generics_ts,
+                ) -> Self
+                where
+                    #derive_input_ident #ty_generics_ts: #bound_ts,
+                {
+                    #call_ts(default, value)
+                }
+            }
         }
     };
 
     Ok(quote! {
         #aux_objects_ts
 
-        #[allow(non_snake_case)]
-        #[derive(settings_schema::Serialize, settings_schema::Deserialize, Clone, Debug)]
-        #vis struct #default_ty_ident {
-            #default_fields_ts
-        }
+        #entry_names_ts
 
-        impl #derive_input_ident {
-            #vis fn schema(default: #default_ty_ident) -> settings_schema::SchemaNode {
-                #schema_code_ts
+        #default_struct_ts
+
+        #from_value_ts
+
+        impl #default_params_ts Default for #default_ty_ts {
+            fn default() -> Self {
+                #default_code_ts
             }
         }
+
+        #trait_impl_ts
     })
 }
 
@@ -398,3 +1493,33 @@ pub fn create_settings_schema_fn_and_default_ty(input: TokenStream) -> TokenStre
         Err(e) => e,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn doc_strings() {
+        // `/// First line` and `///   indented` are desugared to these attributes
+        let attrs: Vec<Attribute> = vec![
+            parse_quote!(#[doc = " First line"]),
+            parse_quote!(#[doc = "   indented"]),
+            parse_quote!(#[doc = ""]),
+            parse_quote!(#[schema(flag = "x")]),
+            parse_quote!(#[doc = " Last line  "]),
+        ];
+        assert_eq!(
+            doc_string(&attrs).as_deref(),
+            Some("First line\n  indented\n\nLast line")
+        );
+
+        // Block comments keep their line breaks and indentation
+        let attrs: Vec<Attribute> = vec![parse_quote!(#[doc = "\n    Block\n      comment\n    "])];
+        assert_eq!(doc_string(&attrs).as_deref(), Some("Block\n  comment"));
+
+        let attrs: Vec<Attribute> = vec![parse_quote!(#[doc = "  "])];
+        assert_eq!(doc_string(&attrs), None);
+        assert_eq!(doc_string(&[]), None);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{error, TResult};
+use syn::{meta::ParseNestedMeta, parenthesized, Attribute, Expr, LitStr, Token};
+
+// Casing transformations of `#[serde(rename_all = "...")]`. Field names are assumed to be in
+// snake_case and variant names in PascalCase, like serde does.
+#[derive(Clone, Copy)]
+pub enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn from_str(rule: &LitStr) -> TResult<Self> {
+        Ok(match rule.value().as_str() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            _ => return error("Unknown serde rename rule", rule),
+        })
+    }
+
+    pub fn apply_to_field(self, field: &str) -> String {
+        match self {
+            Self::Lower | Self::Snake => field.to_owned(),
+            Self::Upper | Self::ScreamingSnake => field.to_ascii_uppercase(),
+            Self::Pascal => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            Self::Camel => {
+                let pascal = Self::Pascal.apply_to_field(field);
+                pascal[..1].to_ascii_lowercase() + &pascal[1..]
+            }
+            Self::Kebab => field.replace('_', "-"),
+            Self::ScreamingKebab => field.replace('_', "-").to_ascii_uppercase(),
+        }
+    }
+
+    pub fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            Self::Pascal => variant.to_owned(),
+            Self::Lower => variant.to_ascii_lowercase(),
+            Self::Upper => variant.to_ascii_uppercase(),
+            Self::Camel => variant[..1].to_ascii_lowercase() + &variant[1..],
+            Self::Snake => {
+                let mut snake = String::new();
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            Self::ScreamingSnake => Self::Snake.apply_to_variant(variant).to_ascii_uppercase(),
+            Self::Kebab => Self::Snake.apply_to_variant(variant).replace('_', "-"),
+            Self::ScreamingKebab => Self::ScreamingSnake
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+        }
+    }
+}
+
+// Subset of the serde attributes that affects the schema. The other attributes are ignored.
+#[derive(Default)]
+pub struct SerdeAttrs {
+    pub rename: Option<String>,
+    pub rename_all: Option<RenameRule>,
+    pub rename_all_fields: Option<RenameRule>,
+    pub flatten: bool,
+    pub skip: bool,
+}
+
+// Parse either `key = "value"` or `key(serialize = "value", ...)`. Only the serialization name is
+// used, since the schema describes the serialized settings.
+fn parse_serialize_name(meta: &ParseNestedMeta) -> syn::Result<Option<LitStr>> {
+    if meta.input.peek(Token![=]) {
+        return Ok(Some(meta.value()?.parse()?));
+    }
+
+    let mut name = None;
+    meta.parse_nested_meta(|meta| {
+        let value = meta.value()?.parse()?;
+        if meta.path.is_ident("serialize") {
+            name = Some(value);
+        }
+
+        Ok(())
+    })?;
+
+    Ok(name)
+}
+
+// Consume the arguments of an ignored serde attribute
+fn skip_meta(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let _content;
+        parenthesized!(_content in meta.input);
+    }
+
+    Ok(())
+}
+
+pub fn parse(attrs: &[Attribute]) -> TResult<SerdeAttrs> {
+    let mut serde_attrs = SerdeAttrs::default();
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        let mut rename_all = None;
+        let mut rename_all_fields = None;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                serde_attrs.rename = parse_serialize_name(&meta)?.map(|name| name.value());
+            } else if meta.path.is_ident("rename_all") {
+                rename_all = parse_serialize_name(&meta)?;
+            } else if meta.path.is_ident("rename_all_fields") {
+                rename_all_fields = parse_serialize_name(&meta)?;
+            } else if meta.path.is_ident("flatten") {
+                serde_attrs.flatten = true;
+            } else if meta.path.is_ident("skip") {
+                serde_attrs.skip = true;
+            } else {
+                skip_meta(&meta)?;
+            }
+
+            Ok(())
+        })
+        .map_err(|e| -> proc_macro::TokenStream { e.to_compile_error().into() })?;
+
+        if let Some(rule) = rename_all {
+            serde_attrs.rename_all = Some(RenameRule::from_str(&rule)?);
+        }
+        if let Some(rule) = rename_all_fields {
+            serde_attrs.rename_all_fields = Some(RenameRule::from_str(&rule)?);
+        }
+    }
+
+    Ok(serde_attrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    const RULES: [RenameRule; 8] = [
+        RenameRule::Lower,
+        RenameRule::Upper,
+        RenameRule::Pascal,
+        RenameRule::Camel,
+        RenameRule::Snake,
+        RenameRule::ScreamingSnake,
+        RenameRule::Kebab,
+        RenameRule::ScreamingKebab,
+    ];
+
+    #[test]
+    fn rename_fields() {
+        let names = RULES.map(|rule| rule.apply_to_field("max_frame_rate"));
+
+        assert_eq!(
+            names,
+            [
+                "max_frame_rate",
+                "MAX_FRAME_RATE",
+                "MaxFrameRate",
+                "maxFrameRate",
+                "max_frame_rate",
+                "MAX_FRAME_RATE",
+                "max-frame-rate",
+                "MAX-FRAME-RATE",
+            ]
+        );
+    }
+
+    #[test]
+    fn rename_variants() {
+        let names = RULES.map(|rule| rule.apply_to_variant("HighQuality"));
+
+        assert_eq!(
+            names,
+            [
+                "highquality",
+                "HIGHQUALITY",
+                "HighQuality",
+                "highQuality",
+                "high_quality",
+                "HIGH_QUALITY",
+                "high-quality",
+                "HIGH-QUALITY",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_rename_rules() {
+        for (name, rule) in [
+            ("lowercase", RenameRule::Lower),
+            ("SCREAMING-KEBAB-CASE", RenameRule::ScreamingKebab),
+        ] {
+            let rule_name = LitStr::new(name, proc_macro2::Span::call_site());
+            let parsed = RenameRule::from_str(&rule_name).ok().unwrap();
+
+            assert_eq!(parsed.apply_to_field("a_b"), rule.apply_to_field("a_b"));
+        }
+    }
+
+    #[test]
+    fn parse_attrs() {
+        let attrs: Vec<Attribute> = vec![
+            parse_quote!(#[doc = "Not a serde attribute"]),
+            parse_quote!(#[serde(rename(serialize = "ser", deserialize = "de"), default)]),
+            parse_quote!(#[serde(rename_all = "camelCase", skip_serializing_if = "x", flatten)]),
+        ];
+        let serde_attrs = parse(&attrs).ok().unwrap();
+
+        assert_eq!(serde_attrs.rename.as_deref(), Some("ser"));
+        assert_eq!(
+            serde_attrs
+                .rename_all
+                .map(|rule| rule.apply_to_field("a_b")),
+            Some("aB".into())
+        );
+        assert!(serde_attrs.rename_all_fields.is_none());
+        assert!(serde_attrs.flatten);
+        assert!(!serde_attrs.skip);
+
+        let serde_attrs = parse(&[parse_quote!(#[serde(skip, rename = "name")])])
+            .ok()
+            .unwrap();
+        assert!(serde_attrs.skip);
+        assert_eq!(serde_attrs.rename.as_deref(), Some("name"));
+    }
+}
@@ -1,10 +1,11 @@
-use crate::{error, suffix_ident, FieldMeta, TResult, TokenStream2};
+use crate::{error, FieldMeta, TResult, TokenStream2};
 use darling::FromMeta;
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
 use quote::{quote, ToTokens};
-use syn::{Expr, GenericArgument, PathArguments, Type, TypeArray, TypePath};
+use syn::{Expr, GenericArgument, Path, PathArguments, Type, TypeArray, TypePath};
 
+#[allow(clippy::large_enum_variant)]
 #[derive(FromMeta)]
 pub enum NumericGuiType {
     Slider {
@@ -18,17 +19,68 @@ pub enum NumericGuiType {
     TextBox,
 }
 
+#[derive(FromMeta)]
+pub struct NormalizedRange {
+    min: Expr,
+    max: Expr,
+}
+
+// Explicit description of a leaf type, set with the `as` attribute. It is used for types that are
+// not recognized by the macro, like type aliases of supported types, or for custom types with the
+// same name of a supported type
+#[derive(FromMeta, Clone, Copy)]
+pub enum TypeOverride {
+    #[darling(rename = "text")]
+    Text,
+    #[darling(rename = "number")]
+    Number,
+    #[darling(rename = "custom")]
+    Custom,
+}
+
+#[derive(FromMeta, Clone, Copy)]
+pub enum DurationUnit {
+    #[darling(rename = "ms")]
+    Milliseconds,
+    #[darling(rename = "s")]
+    Seconds,
+    #[darling(rename = "min")]
+    Minutes,
+}
+
 pub struct TypeSchemaData {
     // Schema representation type, assigned to a specific field in the schema representation struct
     pub default_ty_ts: TokenStream2,
 
     // Schema instatiation code for a specific field
     pub schema_code_ts: TokenStream2,
+
+    // Code that converts `default` (of the schema representation type) into a value of the original
+    // type, choosing the active variant/state and discarding the data needed only by the GUI
+    pub from_default_code_ts: TokenStream2,
+
+    // Code that writes `value` (a reference to the original type) into `default` (of the schema
+    // representation type), returning the updated representation. The data that is not reachable
+    // from the value (inactive variants, disabled content, GUI state) is kept from `default`
+    pub from_value_code_ts: TokenStream2,
+
+    // Code that instantiates the default representation from the field attributes (`default_set`,
+    // `default_enabled`, `default_element`), falling back to `Default::default()` for the leaves
+    pub default_code_ts: TokenStream2,
+
+    // Code that adds the definitions of the named types referenced by the schema to `definitions`
+    // (a `&mut SchemaDefinitions`). It is empty for the builtin leaf types
+    pub definitions_code_ts: TokenStream2,
 }
 
+// Lifetime arguments (like the one of `Cow`) are skipped
 fn get_first_and_only_type_argument(arguments: &PathArguments) -> &Type {
     if let PathArguments::AngleBracketed(args_block) = &arguments {
-        if let GenericArgument::Type(ty) = args_block.args.first().unwrap() {
+        let ty = args_block.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        });
+        if let Some(ty) = ty {
             return ty;
         }
     }
@@ -36,34 +88,266 @@ fn get_first_and_only_type_argument(arguments: &PathArguments) -> &Type {
     unreachable!()
 }
 
-fn forbid_numeric_attrs(field: &FieldMeta, type_str: &str) -> TResult<()> {
-    let tokens = if let Some(arg) = &field.suffix {
-        arg.to_token_stream()
-    } else if field.gui.is_some() {
-        quote!()
+// Arguments of map types. An optional third argument (the hasher) is ignored
+fn get_key_and_value_type_arguments(arguments: &PathArguments) -> (&Type, &Type) {
+    if let PathArguments::AngleBracketed(args_block) = &arguments {
+        let mut types = args_block.args.iter().filter_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        });
+        if let (Some(key_ty), Some(value_ty)) = (types.next(), types.next()) {
+            return (key_ty, value_ty);
+        }
+    }
+    // Fail cases are already handled by the compiler
+    unreachable!()
+}
+
+// Modules where the supported types are defined, by type name. Supported types can be referred to
+// by their name alone or by their full path (with an optional leading `::`), while a path to
+// another module is a custom type with the same name. The second value tells if the type has type
+// arguments.
+fn builtin_type_modules(name: &str) -> Option<(&'static [&'static str], bool)> {
+    Some(match name {
+        "bool" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64"
+        | "i128" | "isize" | "f32" | "f64" | "str" => {
+            (&["std::primitive", "core::primitive"], false)
+        }
+        "String" => (&["std::string", "alloc::string"], false),
+        "Duration" => (&["std::time", "core::time"], false),
+        "Percentage" => (&["settings_schema"], false),
+        "Option" => (&["std::option", "core::option"], true),
+        "Switch" => (&["settings_schema"], true),
+        "RangeInclusive" => (&["std::ops", "core::ops"], true),
+        "Vec" => (&["std::vec", "alloc::vec"], true),
+        "HashMap" => (&["std::collections", "std::collections::hash_map"], true),
+        "BTreeMap" => (
+            &[
+                "std::collections",
+                "std::collections::btree_map",
+                "alloc::collections",
+                "alloc::collections::btree_map",
+            ],
+            true,
+        ),
+        "IndexMap" => (&["indexmap", "indexmap::map"], true),
+        "HashSet" => (&["std::collections", "std::collections::hash_set"], true),
+        "BTreeSet" => (
+            &[
+                "std::collections",
+                "std::collections::btree_set",
+                "alloc::collections",
+                "alloc::collections::btree_set",
+            ],
+            true,
+        ),
+        "Box" => (&["std::boxed", "alloc::boxed"], true),
+        "Rc" => (&["std::rc", "alloc::rc"], true),
+        "Arc" => (&["std::sync", "alloc::sync"], true),
+        "Cow" => (&["std::borrow", "alloc::borrow"], true),
+        _ => return None,
+    })
+}
+
+// Name of the supported type that `path` refers to. Paths with the name of a supported type are
+// custom types if the module or the presence of type arguments do not match, or if the name alone
+// refers to one of the `type_params` of the derived type
+fn builtin_type_name(path: &Path, type_params: &[Ident]) -> Option<String> {
+    let segments = path.segments.iter().collect::<Vec<_>>();
+    let (last, modules) = segments.split_last().unwrap();
+    let name = last.ident.to_string();
+    let (type_modules, generic) = builtin_type_modules(&name)?;
+
+    if generic == last.arguments.is_none() {
+        return None;
+    }
+    if modules.is_empty() && path.leading_colon.is_none() {
+        return if type_params.contains(&last.ident) {
+            None
+        } else {
+            Some(name)
+        };
+    }
+    if modules.iter().any(|segment| !segment.arguments.is_none()) {
+        return None;
+    }
+    let module = modules
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::");
+
+    if type_modules.contains(&module.as_str()) {
+        Some(name)
     } else {
-        return Ok(());
+        None
+    }
+}
+
+fn type_name(ty: &Type, type_params: &[Ident]) -> Option<String> {
+    if let Type::Path(TypePath { qself: None, path }) = ty {
+        builtin_type_name(path, type_params)
+    } else {
+        None
+    }
+}
+
+// Innermost collection of a type, looking through the types that forward the field attributes to
+// their content. `Vec`s of pairs are reported as "dictionary"
+fn innermost_collection(ty: &Type, type_params: &[Ident]) -> Option<String> {
+    let ty_last = match ty {
+        Type::Array(TypeArray { elem, .. }) => return innermost_collection(elem, type_params),
+        Type::Path(TypePath { path, .. }) => path.segments.last()?,
+        _ => return None,
+    };
+    let name = type_name(ty, type_params)?;
+    if !is_wrapper_type(&name) {
+        return None;
+    }
+
+    let mut content_types = match &ty_last.arguments {
+        PathArguments::AngleBracketed(args_block) => args_block
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        _ => vec![],
     };
+    let mut collection_name = name.clone();
+    if ["HashMap", "BTreeMap", "IndexMap"].contains(&name.as_str()) {
+        // Only the values are described with the field attributes
+        content_types = content_types.into_iter().skip(1).take(1).collect();
+    } else if let (true, [Type::Tuple(ty_tuple)]) = (name == "Vec", content_types.as_slice()) {
+        if ty_tuple.elems.len() == 2 && is_string_like(&ty_tuple.elems[0], type_params) {
+            collection_name = "dictionary".into();
+            content_types = vec![&ty_tuple.elems[1]];
+        }
+    }
+
+    let inner_collection = content_types
+        .into_iter()
+        .find_map(|ty| innermost_collection(ty, type_params));
+    if inner_collection.is_none()
+        && !is_pointer_type(&name)
+        && !["Option", "Switch"].contains(&name.as_str())
+    {
+        Some(collection_name)
+    } else {
+        inner_collection
+    }
+}
+
+// `multi_select` applies to the innermost collection of the field type, that must be a `Vec`
+pub fn check_multi_select(ty: &Type, type_params: &[Ident]) -> TResult<()> {
+    match innermost_collection(ty, type_params).as_deref() {
+        Some("Vec") => Ok(()),
+        Some("HashSet") | Some("BTreeSet") => error(
+            "`multi_select` is not needed for sets, their elements are always unique",
+            ty,
+        ),
+        _ => error(
+            "`multi_select` is supported only for `Vec` types that are not dictionaries",
+            ty,
+        ),
+    }
+}
 
-    error(
-        &format!("Unexpected argument for {} type", type_str),
-        tokens,
-    )
+// Smart pointers are transparent: they have the schema and the default representation of the
+// pointed type
+fn is_pointer_type(name: &str) -> bool {
+    ["Box", "Rc", "Arc", "Cow"].contains(&name)
+}
+
+// Dictionary keys and text are stored as `String` in the default representation. Other string
+// types (string slices behind a pointer) are converted with `to_string()` and `From<String>`
+fn is_string_like(ty: &Type, type_params: &[Ident]) -> bool {
+    match type_name(ty, type_params).as_deref() {
+        Some("String") => true,
+        Some(name) if is_pointer_type(name) => {
+            if let Type::Path(TypePath { path, .. }) = ty {
+                let ty_arg =
+                    get_first_and_only_type_argument(&path.segments.last().unwrap().arguments);
+                type_name(ty_arg, type_params).as_deref() == Some("str")
+            } else {
+                false
+            }
+        }
+        _ => false,
+    }
+}
+
+// Leaf attributes are forwarded to every type in the chain. Check that the leaf type supports the
+// ones that have been set. `allowed` contains the attribute names supported by the leaf type.
+fn forbid_leaf_attrs(field: &FieldMeta, type_str: &str, allowed: &[&str]) -> TResult<()> {
+    let set_attrs = [
+        ("gui", field.gui.is_some()),
+        ("suffix", field.suffix.is_some()),
+        ("unit", field.unit.is_some()),
+        ("range_normalized", field.range_normalized.is_some()),
+        ("reference", field.reference),
+    ];
+
+    for (name, is_set) in set_attrs {
+        if is_set && !allowed.contains(&name) {
+            // Type aliases are not resolved by the macro
+            let hint = if type_str == "custom" {
+                ". If the type is an alias of a supported type, spell out the aliased type or set \
+                `alias_of` or `as`"
+            } else {
+                ""
+            };
+
+            return error(
+                &format!(
+                    "Unexpected argument `{}` for {} type{}",
+                    name, type_str, hint
+                ),
+                &field.ty,
+            );
+        }
+    }
+
+    Ok(())
 }
 
 fn bool_type_schema(field: &FieldMeta) -> TResult {
-    forbid_numeric_attrs(field, "bool")?;
+    forbid_leaf_attrs(field, "bool", &[])?;
 
     Ok(quote!(settings_schema::SchemaNode::Boolean { default }))
 }
 
-enum NumberType {
-    UnsignedInteger,
-    SignedInteger,
-    Float,
+fn is_number_type(name: &str) -> bool {
+    [
+        "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+        "f32", "f64",
+    ]
+    .contains(&name)
+}
+
+// Number type of a numeric leaf: a primitive number type, or a type marked with `as = "number"`
+// (usually an alias). The number type (with the integer width) is resolved through the `Number`
+// trait
+fn numeric_type(ty: &Type, field: &FieldMeta) -> Option<TokenStream2> {
+    let is_numeric = match field.as_ {
+        Some(TypeOverride::Number) => true,
+        Some(_) => false,
+        None => type_name(ty, &field.type_params)
+            .as_deref()
+            .is_some_and(is_number_type),
+    };
+
+    if is_numeric {
+        Some(quote!(<#ty as settings_schema::Number>::TYPE))
+    } else {
+        None
+    }
 }
 
-fn number_type_schema(field: &FieldMeta, ty_ident: &Ident, ty: NumberType) -> TResult {
+// Generate the `gui` and `suffix` fields shared by Number and Range nodes. `ty` is the number type
+fn numeric_attrs_schema(field: &FieldMeta, ty: &Type) -> (TokenStream2, TokenStream2) {
     let gui_ts = match &field.gui {
         Some(NumericGuiType::Slider {
             min,
@@ -73,20 +357,20 @@ fn number_type_schema(field: &FieldMeta, ty_ident: &Ident, ty: NumberType) -> TR
         }) => {
             let step_ts = if let Some(step) = step {
                 quote!({
-                    let step: #ty_ident = #step;
-                    Some(step as f64)
+                    let step: #ty = #step;
+                    Some(step.into())
                 })
             } else {
                 quote!(None)
             };
 
             quote!({
-                let min: #ty_ident = #min;
-                let max: #ty_ident = #max;
+                let min: #ty = #min;
+                let max: #ty = #max;
                 debug_assert!(min <= max);
 
                 settings_schema::NumericGuiType::Slider {
-                    range: min as f64..=max as f64,
+                    range: min.into()..=max.into(),
                     step: #step_ts,
                     logarithmic: #logarithmic
                 }
@@ -101,49 +385,621 @@ fn number_type_schema(field: &FieldMeta, ty_ident: &Ident, ty: NumberType) -> TR
         quote!(None)
     };
 
-    let num_ty_string = match ty {
-        NumberType::UnsignedInteger => "UnsignedInteger",
-        NumberType::SignedInteger => "SignedInteger",
-        NumberType::Float => "Float",
-    };
-    let num_ty_ident = Ident::new(num_ty_string, Span::call_site());
+    (gui_ts, suffix_ts)
+}
+
+fn number_type_schema(field: &FieldMeta, ty: &Type, num_ty_ts: TokenStream2) -> TResult {
+    forbid_leaf_attrs(field, "numeric", &["gui", "suffix"])?;
+
+    let (gui_ts, suffix_ts) = numeric_attrs_schema(field, ty);
 
     Ok(quote! {
         settings_schema::SchemaNode::Number {
-            default: default as _,
-            ty: settings_schema::NumberType::#num_ty_ident,
+            default: default.into(),
+            ty: #num_ty_ts,
             gui: #gui_ts,
             suffix: #suffix_ts
         }
     })
 }
 
+fn range_type_schema(field: &FieldMeta, ty_arg: &Type) -> TResult<TypeSchemaData> {
+    let num_ty_ts = if let Some(num_ty_ts) = numeric_type(ty_arg, field) {
+        num_ty_ts
+    } else {
+        return error(
+            "Range bounds must be of numeric type. If the type is an alias of a number type, set \
+            `as = \"number\"`",
+            ty_arg,
+        );
+    };
+    forbid_leaf_attrs(field, "range", &["gui", "suffix"])?;
+
+    let (gui_ts, suffix_ts) = numeric_attrs_schema(field, ty_arg);
+
+    Ok(TypeSchemaData {
+        default_ty_ts: quote!(settings_schema::RangeDefault<#ty_arg>),
+        schema_code_ts: quote! {{
+            debug_assert!(default.start <= default.end);
+
+            settings_schema::SchemaNode::Range {
+                default: [default.start.into(), default.end.into()],
+                ty: #num_ty_ts,
+                gui: #gui_ts,
+                suffix: #suffix_ts
+            }
+        }},
+        from_default_code_ts: quote!(default.start..=default.end),
+        from_value_code_ts: quote! {
+            settings_schema::RangeDefault {
+                start: *value.start(),
+                end: *value.end(),
+            }
+        },
+        default_code_ts: quote!(Default::default()),
+        definitions_code_ts: quote!(),
+    })
+}
+
+fn duration_type_schema(field: &FieldMeta) -> TResult {
+    forbid_leaf_attrs(field, "Duration", &["gui", "unit"])?;
+
+    let gui_ts = match &field.gui {
+        Some(NumericGuiType::Slider {
+            min,
+            max,
+            step,
+            logarithmic,
+        }) => {
+            let step_ts = if let Some(step) = step {
+                quote!(Some(settings_schema::NumberValue::Float((#step) as f64)))
+            } else {
+                quote!(None)
+            };
+
+            quote!({
+                let min = (#min) as f64;
+                let max = (#max) as f64;
+                debug_assert!(min <= max);
+
+                settings_schema::NumericGuiType::Slider {
+                    range: settings_schema::NumberValue::Float(min)
+                        ..=settings_schema::NumberValue::Float(max),
+                    step: #step_ts,
+                    logarithmic: #logarithmic
+                }
+            })
+        }
+        _ => quote!(settings_schema::NumericGuiType::TextBox),
+    };
+
+    let unit_string = match field.unit.unwrap_or(DurationUnit::Seconds) {
+        DurationUnit::Milliseconds => "Milliseconds",
+        DurationUnit::Seconds => "Seconds",
+        DurationUnit::Minutes => "Minutes",
+    };
+    let unit_ident = Ident::new(unit_string, Span::call_site());
+
+    Ok(quote!({
+        let unit = settings_schema::DurationUnit::#unit_ident;
+        settings_schema::SchemaNode::Duration {
+            default: unit.count(default),
+            unit,
+            gui: #gui_ts
+        }
+    }))
+}
+
+fn percentage_type_schema(field: &FieldMeta) -> TResult {
+    forbid_leaf_attrs(field, "Percentage", &["range_normalized"])?;
+
+    let range_ts = if let Some(NormalizedRange { min, max }) = &field.range_normalized {
+        quote!({
+            let min: f32 = #min;
+            let max: f32 = #max;
+            debug_assert!(min <= max);
+
+            Some(min..=max)
+        })
+    } else {
+        quote!(None)
+    };
+
+    Ok(quote! {
+        settings_schema::SchemaNode::Percentage {
+            default_normalized: default.normalized(),
+            range_normalized: #range_ts
+        }
+    })
+}
+
 fn string_type_schema(field: &FieldMeta) -> TResult {
-    forbid_numeric_attrs(field, "String")?;
+    forbid_leaf_attrs(field, "String", &[])?;
 
     Ok(quote!(settings_schema::SchemaNode::Text { default }))
 }
 
-fn custom_leaf_type_schema(ty_ident: &Ident, field: &FieldMeta) -> TResult {
-    forbid_numeric_attrs(field, "custom")?;
+// Schema code of the supported leaf types, whose default representation is the type itself. Returns
+// `None` for the other types
+fn leaf_type_schema(ty: &Type, field: &FieldMeta, name: &str) -> TResult<Option<TokenStream2>> {
+    Ok(Some(match name {
+        "bool" => bool_type_schema(field)?,
+        "String" => string_type_schema(field)?,
+        "Duration" => duration_type_schema(field)?,
+        "Percentage" => percentage_type_schema(field)?,
+        _ => {
+            if is_number_type(name) {
+                number_type_schema(field, ty, quote!(<#ty as settings_schema::Number>::TYPE))?
+            } else {
+                return Ok(None);
+            }
+        }
+    }))
+}
 
-    Ok(quote!(#ty_ident::schema(default)))
+// Leaf types described with the `as` attribute. Text and number types are their own default
+// representation: text types must implement `Display`, and number types must be primitive numbers
+// (through aliases)
+fn override_type_schema(
+    ty: &Type,
+    field: &FieldMeta,
+    type_override: TypeOverride,
+) -> TResult<TypeSchemaData> {
+    if field.with.is_some() {
+        return error("`as` and `with` attributes cannot be used together", ty);
+    }
+
+    let schema_code_ts = match type_override {
+        TypeOverride::Text => {
+            forbid_leaf_attrs(field, "text", &[])?;
+            quote!(settings_schema::SchemaNode::Text {
+                default: default.to_string()
+            })
+        }
+        TypeOverride::Number => {
+            number_type_schema(field, ty, quote!(<#ty as settings_schema::Number>::TYPE))?
+        }
+        TypeOverride::Custom => return custom_type_schema(ty, field),
+    };
+
+    Ok(TypeSchemaData {
+        default_ty_ts: ty.to_token_stream(),
+        schema_code_ts,
+        from_default_code_ts: quote!(default),
+        from_value_code_ts: quote!(value.clone()),
+        default_code_ts: quote!(Default::default()),
+        definitions_code_ts: quote!(),
+    })
+}
+
+// Types that wrap the leaf type, which the `with` attribute is forwarded to
+fn is_wrapper_type(name: &str) -> bool {
+    [
+        "Option", "Switch", "Vec", "HashMap", "BTreeMap", "IndexMap", "HashSet", "BTreeSet",
+    ]
+    .contains(&name)
+        || is_pointer_type(name)
+}
+
+// Custom types (including generic parameters) and tuples are dispatched through the
+// `SettingsSchema` trait. Remote types are dispatched through the `RemoteSettingsSchema` trait of
+// the mirror type set with the `with` attribute. If the mirror path has no generic arguments, the
+// ones of the remote type are used, since the mirror must have the same generic parameters.
+fn custom_type_schema(ty: &Type, field: &FieldMeta) -> Result<TypeSchemaData, TokenStream> {
+    if field.reference {
+        if field.with.is_some() {
+            return error("`reference` is not supported for remote types", ty);
+        }
+
+        return reference_type_schema(ty, field);
+    }
+    forbid_leaf_attrs(field, "custom", &[])?;
+
+    let trait_ts = if let Some(with) = &field.with {
+        let mut with = with.clone();
+        let with_last = with.segments.last_mut().unwrap();
+        if let (PathArguments::None, Type::Path(TypePath { path, .. })) = (&with_last.arguments, ty)
+        {
+            with_last.arguments = path.segments.last().unwrap().arguments.clone();
+        }
+
+        quote!(<#with as settings_schema::RemoteSettingsSchema<#ty>>)
+    } else {
+        quote!(<#ty as settings_schema::SettingsSchema>)
+    };
+
+    Ok(TypeSchemaData {
+        default_ty_ts: quote!(#trait_ts::Default),
+        schema_code_ts: quote!(#trait_ts::schema(default)),
+        from_default_code_ts: quote!(#trait_ts::from_default(default)),
+        from_value_code_ts: quote!(#trait_ts::default_from_value(default, value)),
+        default_code_ts: quote!(Default::default()),
+        definitions_code_ts: quote!(#trait_ts::definitions(definitions);),
+    })
+}
+
+// Recursive fields (of the type being derived) and fields marked with `reference` are referenced
+// by name, instead of being inlined, with a default representation that is instantiated lazily.
+// Otherwise the schema and the default representation of recursive types would be infinite
+fn reference_type_schema(ty: &Type, field: &FieldMeta) -> TResult<TypeSchemaData> {
+    forbid_leaf_attrs(field, "custom", &["reference"])?;
+
+    let trait_ts = quote!(<#ty as settings_schema::SettingsSchema>);
+
+    Ok(TypeSchemaData {
+        default_ty_ts: quote!(settings_schema::ReferenceDefault<#trait_ts::Default>),
+        schema_code_ts: quote!(settings_schema::reference_schema::<#ty>(default)),
+        from_default_code_ts: quote!(#trait_ts::from_default(default.into_inner())),
+        from_value_code_ts: quote! {
+            settings_schema::ReferenceDefault::new(
+                #trait_ts::default_from_value(default.into_inner(), value)
+            )
+        },
+        default_code_ts: quote!(Default::default()),
+        definitions_code_ts: quote!(settings_schema::add_definition::<#ty>(definitions);),
+    })
+}
+
+// Dictionaries are `Vec<(String, X)>` or map types. `ordered` tells if the order of the entries is
+// meaningful and `map` if they are serialized as a map instead of a list of pairs. The default
+// representation is the same for all of them. String keys are converted to `String`, other keys
+// are stored as they are and described through the `SettingsSchema` trait. The schema stores all
+// keys in serialized form
+fn dictionary_type_schema(
+    meta: &FieldMeta,
+    key_ty: &Type,
+    value_ty: &Type,
+    element_default: Option<&Expr>,
+    ordered: bool,
+    map: bool,
+) -> TResult<TypeSchemaData> {
+    let (
+        default_key_ty_ts,
+        key_schema_ts,
+        default_key_code_ts,
+        from_default_key_ts,
+        from_value_key_ts,
+        key_definitions_code_ts,
+    ) = if is_string_like(key_ty, &meta.type_params) {
+        (
+            quote!(std::string::String),
+            quote!(None),
+            quote!(default.key),
+            quote!(key.into()),
+            quote!(key.to_string()),
+            quote!(),
+        )
+    } else {
+        let trait_ts = quote!(<#key_ty as settings_schema::SettingsSchema>);
+        (
+            key_ty.to_token_stream(),
+            quote!(settings_schema::dictionary_key_schema(#trait_ts::schema(
+                #trait_ts::default_from_value(Default::default(), &default.key)
+            ))),
+            quote!(settings_schema::map_key_string(&default.key)),
+            quote!(key),
+            quote!(key.clone()),
+            quote!(#trait_ts::definitions(definitions);),
+        )
+    };
+
+    let type_data = schema(value_ty, meta, None)?;
+    let default_value_ts = element_default_code(value_ty, element_default, &type_data);
+    let TypeSchemaData {
+        default_ty_ts,
+        schema_code_ts,
+        from_default_code_ts,
+        from_value_code_ts,
+        definitions_code_ts,
+        ..
+    } = type_data;
+
+    Ok(TypeSchemaData {
+        default_ty_ts: quote! {
+            settings_schema::DictionaryDefault<#default_ty_ts, #default_key_ty_ts>
+        },
+        schema_code_ts: quote! {{
+            let key = #key_schema_ts;
+            let default_key = #default_key_code_ts;
+            let default_content = settings_schema::dictionary_content(&default.content);
+            let default = default.value;
+            let default_value = Box::new(#schema_code_ts);
+            settings_schema::SchemaNode::Dictionary {
+                default_key,
+                key,
+                default_value,
+                default: default_content,
+                ordered: #ordered,
+                map: #map,
+            }
+        }},
+        from_default_code_ts: quote! {
+            default
+                .content
+                .into_iter()
+                .map(|(key, default)| (#from_default_key_ts, #from_default_code_ts))
+                .collect()
+        },
+        // Entries are matched by key, so data of nested inactive variants is preserved even if
+        // the entries are reordered
+        from_value_code_ts: quote! {{
+            let mut previous_content = default.content;
+            let default_value = default.value;
+            settings_schema::DictionaryDefault {
+                gui_collapsed: default.gui_collapsed,
+                key: default.key,
+                content: value
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = #from_value_key_ts;
+                        let default = previous_content
+                            .iter()
+                            .position(|(previous_key, _)| *previous_key == key)
+                            .map(|index| previous_content.remove(index).1)
+                            .unwrap_or_else(|| default_value.clone());
+                        (key, #from_value_code_ts)
+                    })
+                    .collect(),
+                value: default_value,
+            }
+        }},
+        default_code_ts: quote! {
+            settings_schema::DictionaryDefault {
+                gui_collapsed: false,
+                key: <#default_key_ty_ts as settings_schema::SettingsSchema>::from_default(
+                    Default::default(),
+                ),
+                value: #default_value_ts,
+                content: vec![],
+            }
+        },
+        definitions_code_ts: quote! {
+            #key_definitions_code_ts
+            #definitions_code_ts
+        },
+    })
+}
+
+// `Box`, `Rc` and `Arc` are transparent: the pointed type is described, and the pointer is created
+// when converting from the default representation. String slices behind a pointer (including
+// `Cow<str>`) are described like `String`
+fn pointer_type_schema(
+    meta: &FieldMeta,
+    name: &str,
+    ty_arg: &Type,
+    element_default: Option<&Expr>,
+) -> TResult<TypeSchemaData> {
+    if type_name(ty_arg, &meta.type_params).as_deref() == Some("str") {
+        return Ok(TypeSchemaData {
+            default_ty_ts: quote!(std::string::String),
+            schema_code_ts: string_type_schema(meta)?,
+            from_default_code_ts: quote!(default.into()),
+            from_value_code_ts: quote!(value.to_string()),
+            default_code_ts: quote!(Default::default()),
+            definitions_code_ts: quote!(),
+        });
+    }
+
+    let new_ts = match name {
+        "Box" => quote!(Box::new),
+        "Rc" => quote!(std::rc::Rc::new),
+        "Arc" => quote!(std::sync::Arc::new),
+        _ => return error("`Cow` is supported only for `str`", ty_arg),
+    };
+
+    let TypeSchemaData {
+        default_ty_ts,
+        schema_code_ts,
+        from_default_code_ts,
+        from_value_code_ts,
+        default_code_ts,
+        definitions_code_ts,
+    } = schema(ty_arg, meta, element_default)?;
+
+    Ok(TypeSchemaData {
+        default_ty_ts,
+        schema_code_ts,
+        from_default_code_ts: quote!(#new_ts(#from_default_code_ts)),
+        from_value_code_ts: quote! {{
+            let value = &**value;
+            #from_value_code_ts
+        }},
+        default_code_ts,
+        definitions_code_ts,
+    })
+}
+
+// `Vec` types and sets. Sets (`HashSet`, `BTreeSet` and `Vec` with `multi_select`) contain unique
+// elements, and use the same default representation as vectors
+fn vector_type_schema(
+    meta: &FieldMeta,
+    element_ty: &Type,
+    element_default: Option<&Expr>,
+    set: bool,
+) -> TResult<TypeSchemaData> {
+    let type_data = schema(element_ty, meta, None)?;
+    let default_element_ts = element_default_code(element_ty, element_default, &type_data);
+    let TypeSchemaData {
+        default_ty_ts,
+        schema_code_ts,
+        from_default_code_ts,
+        from_value_code_ts,
+        definitions_code_ts,
+        ..
+    } = type_data;
+
+    let node_ts = if set {
+        quote! {
+            settings_schema::SchemaNode::Set {
+                options: settings_schema::set_options(&default_element),
+                default_element,
+                default: default_content
+            }
+        }
+    } else {
+        quote! {
+            settings_schema::SchemaNode::Vector {
+                default_element,
+                default: default_content
+            }
+        }
+    };
+
+    Ok(TypeSchemaData {
+        default_ty_ts: quote!(settings_schema::VectorDefault<#default_ty_ts>),
+        schema_code_ts: quote! {{
+            let default_content = default
+                .content
+                .iter()
+                .map(settings_schema::default_json_value)
+                .collect();
+            let default = default.element;
+            let default_element = Box::new(#schema_code_ts);
+            #node_ts
+        }},
+        from_default_code_ts: quote! {
+            default
+                .content
+                .into_iter()
+                .map(|default| #from_default_code_ts)
+                .collect()
+        },
+        from_value_code_ts: quote! {{
+            let mut previous_content = default.content.into_iter();
+            let default_element = default.element;
+            settings_schema::VectorDefault {
+                gui_collapsed: default.gui_collapsed,
+                content: value
+                    .iter()
+                    .map(|value| {
+                        let default = previous_content
+                            .next()
+                            .unwrap_or_else(|| default_element.clone());
+                        #from_value_code_ts
+                    })
+                    .collect(),
+                element: default_element,
+            }
+        }},
+        default_code_ts: quote! {
+            settings_schema::VectorDefault {
+                gui_collapsed: false,
+                element: #default_element_ts,
+                content: vec![],
+            }
+        },
+        definitions_code_ts,
+    })
+}
+
+// Code that converts `value_expr` (a value of type `ty`) into its default representation, using
+// the attribute defaults for the data that is not reachable from the value.
+fn value_default_code(ty: &Type, value_expr: &Expr, data: &TypeSchemaData) -> TokenStream2 {
+    let TypeSchemaData {
+        default_ty_ts,
+        from_value_code_ts,
+        default_code_ts,
+        ..
+    } = data;
+
+    quote! {{
+        let default: #default_ty_ts = #default_code_ts;
+        let value: &#ty = &(#value_expr);
+        #from_value_code_ts
+    }}
+}
+
+fn element_default_code(
+    ty: &Type,
+    element_default: Option<&Expr>,
+    data: &TypeSchemaData,
+) -> TokenStream2 {
+    if let Some(expr) = element_default {
+        value_default_code(ty, expr, data)
+    } else {
+        data.default_code_ts.clone()
+    }
+}
+
+// Generate the default value of a field, given its `default` attribute. The explicitly set
+// `default_set` and `default_enabled` attributes take precedence over the state derived from the
+// value, for the chain of Option and Switch types (and pointers) at the root of the field type.
+// This allows to specify the content of a disabled Switch for example.
+pub(crate) fn field_default_code(
+    ty: &Type,
+    meta: &FieldMeta,
+    default_expr: Option<&Expr>,
+    data: &TypeSchemaData,
+) -> TokenStream2 {
+    let default_expr = if let Some(expr) = default_expr {
+        expr
+    } else {
+        return data.default_code_ts.clone();
+    };
+
+    let mut overrides_ts = vec![];
+    let mut accessor_ts = quote!(default);
+    let mut chain_ty = ty;
+    let is_custom = matches!(meta.as_, Some(TypeOverride::Custom));
+    while let (false, Type::Path(TypePath { path, .. })) = (is_custom, chain_ty) {
+        let ty_last = path.segments.last().unwrap();
+        let name = type_name(chain_ty, &meta.type_params);
+        let flag = if name.as_deref() == Some("Option") {
+            meta.default_set.map(|set| quote!(set = #set))
+        } else if name.as_deref() == Some("Switch") {
+            meta.default_enabled
+                .map(|enabled| quote!(enabled = #enabled))
+        } else if name.as_deref().is_some_and(is_pointer_type) {
+            // Pointers are transparent in the default representation
+            chain_ty = get_first_and_only_type_argument(&ty_last.arguments);
+            continue;
+        } else {
+            break;
+        };
+        if let Some(flag) = flag {
+            overrides_ts.push(quote!(#accessor_ts.#flag;));
+        }
+        accessor_ts = quote!(#accessor_ts.content);
+        chain_ty = get_first_and_only_type_argument(&ty_last.arguments);
+    }
+
+    let value_default_ts = value_default_code(ty, default_expr, data);
+
+    quote! {{
+        #[allow(unused_mut)]
+        let mut default = #value_default_ts;
+        #(#overrides_ts)*
+        default
+    }}
 }
 
 // Generate a default representation type and corresponding schema instantiation code.
-// This function calls itself recursively to parse the whole compound type. The recursion degree is
-// 1: only types that have only one type argument can be parsed. Still custom types cannot have type
-// arguments, so they are always the leaf type.
+// This function calls itself recursively to parse the whole compound type, following a single
+// chain of type arguments: most supported types have one type argument, while maps have two (the
+// key is described separately, and the chain continues with the value type). Custom types, generic
+// or not, and tuples are always the leaf type. Recursive fields are leaves too, referenced by name
+// instead of being inlined, and smart pointers are skipped.
 // The meta parameter contains the attributes associated to the curent field: they are forwarded
-// as-is in every recursion step. Most of the attributes are used for numerical leaf types, but
-// there is also the `switch_default` flag that is used by each Switch type inside the type chain.
-pub(crate) fn schema(ty: &Type, meta: &FieldMeta) -> Result<TypeSchemaData, TokenStream> {
+// as-is in every recursion step. Most of the attributes are used for numerical leaf types, while
+// `default_set` and `default_enabled` apply to the Option and Switch types at the root of the
+// chain (see `field_default_code()`).
+// `element_default` is the `default_element` attribute, that is consumed by the first Vec or
+// dictionary found in the chain.
+pub(crate) fn schema(
+    ty: &Type,
+    meta: &FieldMeta,
+    element_default: Option<&Expr>,
+) -> Result<TypeSchemaData, TokenStream> {
     match &ty {
         Type::Array(TypeArray { len, elem, .. }) => {
             let TypeSchemaData {
                 default_ty_ts,
                 schema_code_ts,
-            } = schema(elem, meta)?;
+                from_default_code_ts,
+                from_value_code_ts,
+                default_code_ts,
+                definitions_code_ts,
+            } = schema(elem, meta, element_default)?;
             Ok(TypeSchemaData {
                 default_ty_ts: quote!(settings_schema::ArrayDefault<[#default_ty_ts; #len]>),
                 schema_code_ts: quote! {{
@@ -158,38 +1014,73 @@ pub(crate) fn schema(ty: &Type, meta: &FieldMeta) -> Result<TypeSchemaData, Toke
 
                     settings_schema::SchemaNode::Array(content)
                 }},
+                from_default_code_ts: quote! {
+                    default.content.map(|default| #from_default_code_ts)
+                },
+                from_value_code_ts: quote! {{
+                    let mut values = value.iter();
+                    settings_schema::ArrayDefault {
+                        gui_collapsed: default.gui_collapsed,
+                        content: default.content.map(|default| {
+                            let value = values.next().unwrap();
+                            #from_value_code_ts
+                        }),
+                    }
+                }},
+                default_code_ts: quote! {
+                    settings_schema::ArrayDefault {
+                        gui_collapsed: false,
+                        content: [(); #len].map(|_| #default_code_ts),
+                    }
+                },
+                definitions_code_ts,
             })
         }
         Type::Path(TypePath { path, .. }) => {
             let ty_last = path.segments.last().unwrap();
-            let ty_ident = &ty_last.ident;
-            if matches!(ty_last.arguments, PathArguments::None) {
-                let mut default_ty_ts = None;
-                let schema_code_ts = match ty_ident.to_string().as_str() {
-                    "bool" => bool_type_schema(meta)?,
-                    "u8" | "u16" | "u32" | "u64" | "usize" => {
-                        number_type_schema(meta, ty_ident, NumberType::UnsignedInteger)?
-                    }
-                    "i8" | "i16" | "i32" | "i64" | "isize" => {
-                        number_type_schema(meta, ty_ident, NumberType::SignedInteger)?
-                    }
-                    "f32" | "f64" => number_type_schema(meta, ty_ident, NumberType::Float)?,
-                    "String" => string_type_schema(meta)?,
-                    "u128" | "i128" => error("Unsupported integer size", ty_ident)?,
-                    _ => {
-                        default_ty_ts = Some(suffix_ident(ty_ident, "Default").to_token_stream());
-                        custom_leaf_type_schema(ty_ident, meta)?
-                    }
-                };
+            let name = type_name(ty, &meta.type_params);
+            let name = name.as_deref().unwrap_or("");
+            // The `as` attribute applies to the leaf type, and to the bounds of ranges, except for
+            // `as = "custom"` that applies to the whole type
+            let is_leaf = !is_wrapper_type(name) && name != "RangeInclusive";
+            // Only the bare name of the derived type (or `Self`, already replaced with it) refers
+            // to it. Qualified paths, like `std::time::Duration` inside a type named `Duration`,
+            // name other types
+            let is_recursive = path.leading_colon.is_none()
+                && path.segments.len() == 1
+                && meta.recursive_ident.as_ref() == Some(&ty_last.ident);
+            if meta.with.is_some() && !is_wrapper_type(name) {
+                custom_type_schema(ty, meta)
+            } else if is_recursive {
+                reference_type_schema(ty, meta)
+            } else if let Some(TypeOverride::Custom) = meta.as_ {
+                override_type_schema(ty, meta, TypeOverride::Custom)
+            } else if let (Some(type_override), true) = (meta.as_, is_leaf) {
+                override_type_schema(ty, meta, type_override)
+            } else if let Some(schema_code_ts) = leaf_type_schema(ty, meta, name)? {
                 Ok(TypeSchemaData {
-                    default_ty_ts: default_ty_ts.unwrap_or_else(|| ty_ident.to_token_stream()),
+                    default_ty_ts: ty.to_token_stream(),
                     schema_code_ts,
+                    from_default_code_ts: quote!(default),
+                    from_value_code_ts: quote!(value.clone()),
+                    default_code_ts: quote!(Default::default()),
+                    definitions_code_ts: quote!(),
                 })
-            } else if ty_ident == "Option" {
+            } else if name == "RangeInclusive" {
+                range_type_schema(meta, get_first_and_only_type_argument(&ty_last.arguments))
+            } else if name == "Option" {
                 let TypeSchemaData {
                     default_ty_ts,
                     schema_code_ts,
-                } = schema(get_first_and_only_type_argument(&ty_last.arguments), meta)?;
+                    from_default_code_ts,
+                    from_value_code_ts,
+                    default_code_ts,
+                    definitions_code_ts,
+                } = schema(
+                    get_first_and_only_type_argument(&ty_last.arguments),
+                    meta,
+                    element_default,
+                )?;
                 Ok(TypeSchemaData {
                     default_ty_ts: quote!(settings_schema::OptionalDefault<#default_ty_ts>),
                     schema_code_ts: quote! {{
@@ -198,12 +1089,49 @@ pub(crate) fn schema(ty: &Type, meta: &FieldMeta) -> Result<TypeSchemaData, Toke
                         let content = Box::new(#schema_code_ts);
                         settings_schema::SchemaNode::Optional { default_set, content }
                     }},
+                    from_default_code_ts: quote! {
+                        if default.set {
+                            let default = default.content;
+                            Some(#from_default_code_ts)
+                        } else {
+                            None
+                        }
+                    },
+                    from_value_code_ts: quote! {
+                        settings_schema::OptionalDefault {
+                            set: value.is_some(),
+                            content: if let Some(value) = value {
+                                let default = default.content;
+                                #from_value_code_ts
+                            } else {
+                                default.content
+                            },
+                        }
+                    },
+                    default_code_ts: {
+                        let set = meta.default_set.unwrap_or(false);
+                        quote! {
+                            settings_schema::OptionalDefault {
+                                set: #set,
+                                content: #default_code_ts,
+                            }
+                        }
+                    },
+                    definitions_code_ts,
                 })
-            } else if ty_ident == "Switch" {
+            } else if name == "Switch" {
                 let TypeSchemaData {
                     default_ty_ts,
                     schema_code_ts,
-                } = schema(get_first_and_only_type_argument(&ty_last.arguments), meta)?;
+                    from_default_code_ts,
+                    from_value_code_ts,
+                    default_code_ts,
+                    definitions_code_ts,
+                } = schema(
+                    get_first_and_only_type_argument(&ty_last.arguments),
+                    meta,
+                    element_default,
+                )?;
                 Ok(TypeSchemaData {
                     default_ty_ts: quote!(settings_schema::SwitchDefault<#default_ty_ts>),
                     schema_code_ts: quote! {{
@@ -215,70 +1143,86 @@ pub(crate) fn schema(ty: &Type, meta: &FieldMeta) -> Result<TypeSchemaData, Toke
                             content
                         }
                     }},
+                    from_default_code_ts: quote! {
+                        if default.enabled {
+                            let default = default.content;
+                            settings_schema::Switch::Enabled(#from_default_code_ts)
+                        } else {
+                            settings_schema::Switch::Disabled
+                        }
+                    },
+                    from_value_code_ts: quote! {
+                        settings_schema::SwitchDefault {
+                            enabled: value.enabled(),
+                            content: if let settings_schema::Switch::Enabled(value) = value {
+                                let default = default.content;
+                                #from_value_code_ts
+                            } else {
+                                default.content
+                            },
+                        }
+                    },
+                    default_code_ts: {
+                        let enabled = meta.default_enabled.unwrap_or(false);
+                        quote! {
+                            settings_schema::SwitchDefault {
+                                enabled: #enabled,
+                                content: #default_code_ts,
+                            }
+                        }
+                    },
+                    definitions_code_ts,
                 })
-            } else if ty_ident == "Vec" {
+            } else if ["HashMap", "BTreeMap", "IndexMap"].contains(&name) {
+                let (key_ty, value_ty) = get_key_and_value_type_arguments(&ty_last.arguments);
+                dictionary_type_schema(
+                    meta,
+                    key_ty,
+                    value_ty,
+                    element_default,
+                    name == "IndexMap",
+                    true,
+                )
+            } else if name == "Vec" {
                 let ty_arg = get_first_and_only_type_argument(&ty_last.arguments);
-                if let Type::Tuple(ty_tuple) = ty_arg {
-                    if ty_tuple.elems.len() != 2 {
-                        error("Expected two arguments", &ty_tuple.elems)
-                    } else if ty_tuple.elems[0].to_token_stream().to_string() != "String" {
-                        error("First argument must be a `String`", &ty_tuple.elems)
-                    } else {
-                        let ty_arg = &ty_tuple.elems[1];
-                        let TypeSchemaData {
-                            default_ty_ts,
-                            schema_code_ts,
-                        } = schema(ty_arg, meta)?;
-                        Ok(TypeSchemaData {
-                            default_ty_ts: quote! {
-                                settings_schema::DictionaryDefault<#default_ty_ts>
-                            },
-                            schema_code_ts: quote! {{
-                                let default_content =
-                                    serde_json::from_value(
-                                        serde_json::to_value(default.content).unwrap()
-                                    )
-                                    .unwrap();
-                                let default_key = default.key;
-                                let default = default.value;
-                                let default_value = Box::new(#schema_code_ts);
-                                settings_schema::SchemaNode::Dictionary {
-                                    default_key,
-                                    default_value,
-                                    default: default_content
-                                }
-                            }},
-                        })
+                match ty_arg {
+                    Type::Tuple(ty_tuple)
+                        if ty_tuple.elems.len() == 2
+                            && is_string_like(&ty_tuple.elems[0], &meta.type_params) =>
+                    {
+                        dictionary_type_schema(
+                            meta,
+                            &ty_tuple.elems[0],
+                            &ty_tuple.elems[1],
+                            element_default,
+                            true,
+                            false,
+                        )
                     }
-                } else {
-                    let TypeSchemaData {
-                        default_ty_ts,
-                        schema_code_ts,
-                    } = schema(ty_arg, meta)?;
-                    Ok(TypeSchemaData {
-                        default_ty_ts: quote!(settings_schema::VectorDefault<#default_ty_ts>),
-                        schema_code_ts: quote! {{
-                            let default_content =
-                                serde_json::from_value(
-                                    serde_json::to_value(default.content).unwrap()
-                                )
-                                .unwrap();
-                            let default = default.element;
-                            let default_element = Box::new(#schema_code_ts);
-                            settings_schema::SchemaNode::Vector {
-                                default_element,
-                                default: default_content
-                            }
-                        }},
-                    })
+                    // `multi_select` applies to the innermost `Vec`
+                    _ => vector_type_schema(
+                        meta,
+                        ty_arg,
+                        element_default,
+                        meta.multi_select
+                            && innermost_collection(ty_arg, &meta.type_params).is_none(),
+                    ),
                 }
-            } else {
-                error(
-                    "Type arguments are supported only for Option, Switch, Vec",
-                    ty_last,
+            } else if name == "HashSet" || name == "BTreeSet" {
+                let ty_arg = get_first_and_only_type_argument(&ty_last.arguments);
+                vector_type_schema(meta, ty_arg, element_default, true)
+            } else if is_pointer_type(name) {
+                pointer_type_schema(
+                    meta,
+                    name,
+                    get_first_and_only_type_argument(&ty_last.arguments),
+                    element_default,
                 )
+            } else {
+                custom_type_schema(ty, meta)
             }
         }
+        Type::Tuple(_) => custom_type_schema(ty, meta),
         _ => error("Unsupported type", ty),
     }
 }
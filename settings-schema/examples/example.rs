@@ -1,33 +1,64 @@
 #![allow(dead_code)]
 
 use settings_schema::*;
+use std::{ops::RangeInclusive, time::Duration};
 
-#[derive(SettingsSchema)]
+#[derive(SettingsSchema, Serialize, Deserialize, Debug)]
 #[schema(collapsible)]
 struct TestStruct {
-    #[schema(gui(slider(min = 10, max = 100, step = 10, logarithmic)))]
+    #[schema(gui(slider(min = 10, max = 100, step = 10, logarithmic)), default = Some(50))]
     optional: Option<usize>,
 
-    #[schema(strings(my_string = "Switch"))]
+    #[schema(
+        strings(my_string = "Switch"),
+        default = Switch::Enabled("test".into()),
+        default_enabled = false
+    )]
     switch: Switch<String>,
 
-    #[schema(gui(slider(min = -0.5, max = 0.5, step = 0.1)), suffix = "m")]
+    #[schema(
+        gui(slider(min = -0.5, max = 0.5, step = 0.1)),
+        suffix = "m",
+        default = [0.0, 0.2]
+    )]
     array: [f32; 2],
 
+    /// List of values.
+    /// New elements are set to zero.
     vec: Vec<f32>,
 
-    #[schema(flag = "advanced")]
+    #[schema(gui(slider(min = 0, max = 255)), default = 20..=80)]
+    range: RangeInclusive<u8>,
+
+    #[schema(
+        unit = "ms",
+        gui(slider(min = 0, max = 500, step = 10)),
+        default = Duration::from_millis(60)
+    )]
+    latency: Duration,
+
+    #[schema(range_normalized(min = 0.0, max = 0.5), default = Percentage::new(20))]
+    opacity: Percentage,
+
+    #[schema(
+        flag = "advanced",
+        default = vec![("key 1".into(), false), ("key 2".into(), true)]
+    )]
     dictionary: Vec<(String, bool)>,
 }
 
-#[derive(SettingsSchema)]
+#[derive(SettingsSchema, Serialize, Deserialize, Debug)]
 #[schema(gui = "button_group")]
+#[serde(rename_all = "snake_case")]
 enum TestEnum {
     #[schema(strings(display_name = "First option"))]
     Variant,
+
+    /// Variant with a number
+    #[schema(default = 3)]
     Value(i32),
 
-    #[schema(collapsible)]
+    #[schema(collapsible, default_variant)]
     Block {
         #[schema(strings(hint = "This is a test"))]
         test_struct: TestStruct,
@@ -35,49 +66,42 @@ enum TestEnum {
 }
 
 fn main() {
-    let default = TestEnumDefault {
-        variant: TestEnumDefaultVariant::Block,
-        Value: 3,
-        Block: TestEnumBlockDefault {
-            gui_collapsed: true,
-            test_struct: TestStructDefault {
-                gui_collapsed: false,
-                optional: OptionalDefault {
-                    set: true,
-                    content: 50,
-                },
-                switch: SwitchDefault {
-                    enabled: false,
-                    content: "test".into(),
-                },
-                array: ArrayDefault {
-                    gui_collapsed: false,
-                    content: [0.0, 0.2],
-                },
-                vec: VectorDefault {
-                    gui_collapsed: true,
-                    element: 0.0,
-                    content: vec![],
-                },
-                dictionary: DictionaryDefault {
-                    gui_collapsed: true,
-                    key: "key".into(),
-                    value: false,
-                    content: vec![("key 1".into(), false), ("key 2".into(), true)],
-                },
-            },
-        },
-    };
+    let default = TestEnumDefault::default();
 
     println!(
         "default:\n{}\n",
         serde_json::to_string_pretty(&default).unwrap()
     );
 
+    let value = TestEnum::from(default.clone());
+
+    println!("value:\n{:#?}\n", value);
+
+    // Switching variant keeps the data of the other variants
+    let session = TestEnumDefault::from_value(default.clone(), &TestEnum::Value(10));
+
+    println!(
+        "session:\n{}\n",
+        serde_json::to_string_pretty(&session).unwrap()
+    );
+
     let schema = TestEnum::schema(default);
 
     println!(
         "schema:\n{}\n",
         serde_json::to_string_pretty(&schema).unwrap()
     );
+
+    println!(
+        "JSON schema:\n{}\n",
+        serde_json::to_string_pretty(&schema.to_json_schema()).unwrap()
+    );
+
+    let session = serde_json::json!({ "value": 1.5 });
+    if let Err(errors) = schema.validate(&session) {
+        println!("validation errors:");
+        for error in errors {
+            println!("{}", error);
+        }
+    }
 }
@@ -0,0 +1,588 @@
+use crate::{
+    default_json_value, dictionary_content, dictionary_key_schema, map_key_string, schema_name_of,
+    set_options, ArrayDefault, DictionaryDefault, DurationUnit, Number, NumberType, NumericGuiType,
+    OptionalDefault, Percentage, RangeDefault, SchemaDefinitions, SchemaEntry, SchemaNode,
+    SettingsSchema, Switch, SwitchDefault, VectorDefault,
+};
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+    ops::RangeInclusive,
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+
+// Implementations for the std and runtime types. They are used for the type arguments of generic
+// derived types and for the types that the derive macro dispatches through the trait (like
+// tuples). The field attributes (like `gui` and `default_set`) are not available here, so the
+// default GUI and states are used.
+
+impl SettingsSchema for bool {
+    type Default = bool;
+
+    fn schema(default: bool) -> SchemaNode {
+        SchemaNode::Boolean { default }
+    }
+
+    fn from_default(default: bool) -> Self {
+        default
+    }
+
+    fn default_from_value(_: bool, value: &Self) -> bool {
+        *value
+    }
+
+    fn schema_name() -> Option<String> {
+        Some("bool".into())
+    }
+}
+
+impl SettingsSchema for String {
+    type Default = String;
+
+    fn schema(default: String) -> SchemaNode {
+        SchemaNode::Text { default }
+    }
+
+    fn from_default(default: String) -> Self {
+        default
+    }
+
+    fn default_from_value(_: String, value: &Self) -> String {
+        value.clone()
+    }
+
+    fn schema_name() -> Option<String> {
+        Some("String".into())
+    }
+}
+
+macro_rules! impl_number {
+    ($ty:ty, $number_ty:expr) => {
+        impl Number for $ty {
+            const TYPE: NumberType = $number_ty;
+        }
+
+        impl SettingsSchema for $ty {
+            type Default = $ty;
+
+            fn schema(default: $ty) -> SchemaNode {
+                SchemaNode::Number {
+                    default: default.into(),
+                    ty: $number_ty,
+                    gui: NumericGuiType::TextBox,
+                    suffix: None,
+                }
+            }
+
+            fn from_default(default: $ty) -> Self {
+                default
+            }
+
+            fn default_from_value(_: $ty, value: &Self) -> $ty {
+                *value
+            }
+
+            fn schema_name() -> Option<String> {
+                Some(stringify!($ty).into())
+            }
+        }
+
+        impl SettingsSchema for RangeInclusive<$ty> {
+            type Default = RangeDefault<$ty>;
+
+            fn schema(default: RangeDefault<$ty>) -> SchemaNode {
+                debug_assert!(default.start <= default.end);
+
+                SchemaNode::Range {
+                    default: [default.start.into(), default.end.into()],
+                    ty: $number_ty,
+                    gui: NumericGuiType::TextBox,
+                    suffix: None,
+                }
+            }
+
+            fn from_default(default: RangeDefault<$ty>) -> Self {
+                default.start..=default.end
+            }
+
+            fn default_from_value(_: RangeDefault<$ty>, value: &Self) -> RangeDefault<$ty> {
+                RangeDefault {
+                    start: *value.start(),
+                    end: *value.end(),
+                }
+            }
+
+            fn schema_name() -> Option<String> {
+                Some(concat!("RangeInclusive<", stringify!($ty), ">").into())
+            }
+        }
+    };
+}
+
+impl_number!(u8, NumberType::UnsignedInteger { bits: u8::BITS });
+impl_number!(u16, NumberType::UnsignedInteger { bits: u16::BITS });
+impl_number!(u32, NumberType::UnsignedInteger { bits: u32::BITS });
+impl_number!(u64, NumberType::UnsignedInteger { bits: u64::BITS });
+impl_number!(u128, NumberType::UnsignedInteger { bits: u128::BITS });
+impl_number!(usize, NumberType::UnsignedInteger { bits: usize::BITS });
+impl_number!(i8, NumberType::SignedInteger { bits: i8::BITS });
+impl_number!(i16, NumberType::SignedInteger { bits: i16::BITS });
+impl_number!(i32, NumberType::SignedInteger { bits: i32::BITS });
+impl_number!(i64, NumberType::SignedInteger { bits: i64::BITS });
+impl_number!(i128, NumberType::SignedInteger { bits: i128::BITS });
+impl_number!(isize, NumberType::SignedInteger { bits: isize::BITS });
+impl_number!(f32, NumberType::Float);
+impl_number!(f64, NumberType::Float);
+
+impl SettingsSchema for Duration {
+    type Default = Duration;
+
+    fn schema(default: Duration) -> SchemaNode {
+        let unit = DurationUnit::Seconds;
+        SchemaNode::Duration {
+            default: unit.count(default),
+            unit,
+            gui: NumericGuiType::TextBox,
+        }
+    }
+
+    fn from_default(default: Duration) -> Self {
+        default
+    }
+
+    fn default_from_value(_: Duration, value: &Self) -> Duration {
+        *value
+    }
+
+    fn schema_name() -> Option<String> {
+        Some("Duration".into())
+    }
+}
+
+impl SettingsSchema for Percentage {
+    type Default = Percentage;
+
+    fn schema(default: Percentage) -> SchemaNode {
+        SchemaNode::Percentage {
+            default_normalized: default.normalized(),
+            range_normalized: None,
+        }
+    }
+
+    fn from_default(default: Percentage) -> Self {
+        default
+    }
+
+    fn default_from_value(_: Percentage, value: &Self) -> Percentage {
+        *value
+    }
+
+    fn schema_name() -> Option<String> {
+        Some("Percentage".into())
+    }
+}
+
+impl<T: SettingsSchema> SettingsSchema for Option<T> {
+    type Default = OptionalDefault<T::Default>;
+
+    fn schema(default: Self::Default) -> SchemaNode {
+        SchemaNode::Optional {
+            default_set: default.set,
+            content: Box::new(T::schema(default.content)),
+        }
+    }
+
+    fn from_default(default: Self::Default) -> Self {
+        if default.set {
+            Some(T::from_default(default.content))
+        } else {
+            None
+        }
+    }
+
+    fn default_from_value(default: Self::Default, value: &Self) -> Self::Default {
+        OptionalDefault {
+            set: value.is_some(),
+            content: if let Some(value) = value {
+                T::default_from_value(default.content, value)
+            } else {
+                default.content
+            },
+        }
+    }
+
+    fn schema_name() -> Option<String> {
+        Some(format!("Option<{}>", schema_name_of::<T>()))
+    }
+
+    fn definitions(definitions: &mut SchemaDefinitions) {
+        T::definitions(definitions);
+    }
+}
+
+impl<T: SettingsSchema> SettingsSchema for Switch<T> {
+    type Default = SwitchDefault<T::Default>;
+
+    fn schema(default: Self::Default) -> SchemaNode {
+        SchemaNode::Switch {
+            default_enabled: default.enabled,
+            content: Box::new(T::schema(default.content)),
+        }
+    }
+
+    fn from_default(default: Self::Default) -> Self {
+        if default.enabled {
+            Switch::Enabled(T::from_default(default.content))
+        } else {
+            Switch::Disabled
+        }
+    }
+
+    fn default_from_value(default: Self::Default, value: &Self) -> Self::Default {
+        SwitchDefault {
+            enabled: value.enabled(),
+            content: if let Switch::Enabled(value) = value {
+                T::default_from_value(default.content, value)
+            } else {
+                default.content
+            },
+        }
+    }
+
+    fn schema_name() -> Option<String> {
+        Some(format!("Switch<{}>", schema_name_of::<T>()))
+    }
+
+    fn definitions(definitions: &mut SchemaDefinitions) {
+        T::definitions(definitions);
+    }
+}
+
+impl<T: SettingsSchema> SettingsSchema for Vec<T> {
+    type Default = VectorDefault<T::Default>;
+
+    fn schema(default: Self::Default) -> SchemaNode {
+        SchemaNode::Vector {
+            default_element: Box::new(T::schema(default.element)),
+            default: default.content.iter().map(default_json_value).collect(),
+        }
+    }
+
+    fn from_default(default: Self::Default) -> Self {
+        default.content.into_iter().map(T::from_default).collect()
+    }
+
+    fn default_from_value(default: Self::Default, value: &Self) -> Self::Default {
+        let mut previous_content = default.content.into_iter();
+        let default_element = default.element;
+        VectorDefault {
+            gui_collapsed: default.gui_collapsed,
+            content: value
+                .iter()
+                .map(|value| {
+                    let default = previous_content
+                        .next()
+                        .unwrap_or_else(|| default_element.clone());
+                    T::default_from_value(default, value)
+                })
+                .collect(),
+            element: default_element,
+        }
+    }
+
+    fn schema_name() -> Option<String> {
+        Some(format!("Vec<{}>", schema_name_of::<T>()))
+    }
+
+    fn definitions(definitions: &mut SchemaDefinitions) {
+        T::definitions(definitions);
+    }
+}
+
+// Sets use the same default representation of vectors
+macro_rules! impl_set {
+    ($set:ident, $($bounds:tt)+) => {
+        impl<T: SettingsSchema + $($bounds)+> SettingsSchema for $set<T> {
+            type Default = VectorDefault<T::Default>;
+
+            fn schema(default: Self::Default) -> SchemaNode {
+                let default_element = Box::new(T::schema(default.element));
+                SchemaNode::Set {
+                    options: set_options(&default_element),
+                    default_element,
+                    default: default
+                        .content
+                        .iter()
+                        .map(default_json_value)
+                        .collect(),
+                }
+            }
+
+            fn from_default(default: Self::Default) -> Self {
+                default.content.into_iter().map(T::from_default).collect()
+            }
+
+            fn default_from_value(default: Self::Default, value: &Self) -> Self::Default {
+                let mut previous_content = default.content.into_iter();
+                let default_element = default.element;
+                VectorDefault {
+                    gui_collapsed: default.gui_collapsed,
+                    content: value
+                        .iter()
+                        .map(|value| {
+                            let default = previous_content
+                                .next()
+                                .unwrap_or_else(|| default_element.clone());
+                            T::default_from_value(default, value)
+                        })
+                        .collect(),
+                    element: default_element,
+                }
+            }
+
+            fn schema_name() -> Option<String> {
+                Some(format!(concat!(stringify!($set), "<{}>"), schema_name_of::<T>()))
+            }
+
+            fn definitions(definitions: &mut SchemaDefinitions) {
+                T::definitions(definitions);
+            }
+        }
+    };
+}
+
+impl_set!(HashSet, Eq + Hash);
+impl_set!(BTreeSet, Ord);
+
+// `ordered` tells if the order of the entries is meaningful
+macro_rules! impl_map {
+    ($map:ident, $ordered:expr, $($key_bounds:tt)+) => {
+        impl<K, T> SettingsSchema for $map<K, T>
+        where
+            K: SettingsSchema + Serialize + DeserializeOwned + Clone + Debug + $($key_bounds)+,
+            T: SettingsSchema,
+        {
+            type Default = DictionaryDefault<T::Default, K>;
+
+            fn schema(default: Self::Default) -> SchemaNode {
+                SchemaNode::Dictionary {
+                    default_key: map_key_string(&default.key),
+                    key: dictionary_key_schema(K::schema(K::default_from_value(
+                        Default::default(),
+                        &default.key,
+                    ))),
+                    default_value: Box::new(T::schema(default.value)),
+                    default: dictionary_content(&default.content),
+                    ordered: $ordered,
+                    map: true,
+                }
+            }
+
+            fn from_default(default: Self::Default) -> Self {
+                default
+                    .content
+                    .into_iter()
+                    .map(|(key, default)| (key, T::from_default(default)))
+                    .collect()
+            }
+
+            fn default_from_value(default: Self::Default, value: &Self) -> Self::Default {
+                let mut previous_content = default.content;
+                let default_value = default.value;
+                DictionaryDefault {
+                    gui_collapsed: default.gui_collapsed,
+                    key: default.key,
+                    content: value
+                        .iter()
+                        .map(|(key, value)| {
+                            let default = previous_content
+                                .iter()
+                                .position(|(previous_key, _)| previous_key == key)
+                                .map(|index| previous_content.remove(index).1)
+                                .unwrap_or_else(|| default_value.clone());
+                            (key.clone(), T::default_from_value(default, value))
+                        })
+                        .collect(),
+                    value: default_value,
+                }
+            }
+
+            fn schema_name() -> Option<String> {
+                Some(format!(
+                    concat!(stringify!($map), "<{}, {}>"),
+                    schema_name_of::<K>(),
+                    schema_name_of::<T>()
+                ))
+            }
+
+            fn definitions(definitions: &mut SchemaDefinitions) {
+                K::definitions(definitions);
+                T::definitions(definitions);
+            }
+        }
+    };
+}
+
+impl_map!(HashMap, false, Eq + Hash);
+impl_map!(BTreeMap, false, Ord);
+#[cfg(feature = "indexmap")]
+impl_map!(IndexMap, true, Eq + Hash);
+
+// Smart pointers are transparent
+macro_rules! impl_pointer {
+    ($pointer:ty) => {
+        impl<T: SettingsSchema> SettingsSchema for $pointer {
+            type Default = T::Default;
+
+            fn schema(default: T::Default) -> SchemaNode {
+                T::schema(default)
+            }
+
+            fn from_default(default: T::Default) -> Self {
+                <$pointer>::new(T::from_default(default))
+            }
+
+            fn default_from_value(default: T::Default, value: &Self) -> T::Default {
+                T::default_from_value(default, value)
+            }
+
+            fn schema_name() -> Option<String> {
+                T::schema_name()
+            }
+
+            fn definition_schema(default: T::Default) -> SchemaNode {
+                T::definition_schema(default)
+            }
+
+            fn definitions(definitions: &mut SchemaDefinitions) {
+                T::definitions(definitions);
+            }
+        }
+    };
+}
+
+impl_pointer!(Box<T>);
+impl_pointer!(Rc<T>);
+impl_pointer!(Arc<T>);
+
+// String slices behind a pointer are described like `String`
+macro_rules! impl_str_pointer {
+    ($pointer:ty) => {
+        impl SettingsSchema for $pointer {
+            type Default = String;
+
+            fn schema(default: String) -> SchemaNode {
+                SchemaNode::Text { default }
+            }
+
+            fn from_default(default: String) -> Self {
+                default.into()
+            }
+
+            fn default_from_value(_: String, value: &Self) -> String {
+                value.to_string()
+            }
+
+            fn schema_name() -> Option<String> {
+                Some(stringify!($pointer).into())
+            }
+        }
+    };
+}
+
+impl_str_pointer!(Box<str>);
+impl_str_pointer!(Rc<str>);
+impl_str_pointer!(Arc<str>);
+impl_str_pointer!(Cow<'static, str>);
+
+// serde supports only arrays up to 32 elements
+impl<T: SettingsSchema, const N: usize> SettingsSchema for [T; N]
+where
+    [T::Default; N]: Serialize + DeserializeOwned,
+{
+    type Default = ArrayDefault<[T::Default; N]>;
+
+    fn schema(default: Self::Default) -> SchemaNode {
+        SchemaNode::Array(Vec::from(default.content.map(T::schema)))
+    }
+
+    fn from_default(default: Self::Default) -> Self {
+        default.content.map(T::from_default)
+    }
+
+    fn default_from_value(default: Self::Default, value: &Self) -> Self::Default {
+        let mut values = value.iter();
+        ArrayDefault {
+            gui_collapsed: default.gui_collapsed,
+            content: default
+                .content
+                .map(|default| T::default_from_value(default, values.next().unwrap())),
+        }
+    }
+
+    fn schema_name() -> Option<String> {
+        Some(format!("[{}; {}]", schema_name_of::<T>(), N))
+    }
+
+    fn definitions(definitions: &mut SchemaDefinitions) {
+        T::definitions(definitions);
+    }
+}
+
+// Tuples have the same schema as tuple structs
+macro_rules! impl_tuple {
+    ($($ty:ident $index:tt),+) => {
+        impl<$($ty: SettingsSchema),+> SettingsSchema for ($($ty,)+) {
+            type Default = ($($ty::Default,)+);
+
+            fn schema(default: Self::Default) -> SchemaNode {
+                SchemaNode::Tuple {
+                    entries: vec![$(SchemaEntry {
+                        name: stringify!($index).into(),
+                        strings: HashMap::new(),
+                        flags: HashSet::new(),
+                        content: $ty::schema(default.$index),
+                    }),+],
+                    strings: HashMap::new(),
+                }
+            }
+
+            fn from_default(default: Self::Default) -> Self {
+                ($($ty::from_default(default.$index),)+)
+            }
+
+            fn default_from_value(default: Self::Default, value: &Self) -> Self::Default {
+                ($($ty::default_from_value(default.$index, &value.$index),)+)
+            }
+
+            fn schema_name() -> Option<String> {
+                let names: &[String] = &[$(schema_name_of::<$ty>()),+];
+                if names.len() == 1 {
+                    Some(format!("({},)", names[0]))
+                } else {
+                    Some(format!("({})", names.join(", ")))
+                }
+            }
+
+            fn definitions(definitions: &mut SchemaDefinitions) {
+                $($ty::definitions(definitions);)+
+            }
+        }
+    };
+}
+
+impl_tuple!(A 0);
+impl_tuple!(A 0, B 1);
+impl_tuple!(A 0, B 1, C 2);
+impl_tuple!(A 0, B 1, C 2, D 3);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
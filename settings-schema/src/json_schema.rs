@@ -0,0 +1,625 @@
+use crate::{NumberType, NumberValue, NumericGuiType, SchemaDefinitions, SchemaEntry, SchemaNode};
+use serde_json::{json, Map, Value};
+use std::{collections::HashMap, convert::TryFrom, time::Duration};
+
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+// Encode a number in the JSON form produced by serde for the original Rust type. JSON values can
+// hold integers of up to 64 bits, wider ones are approximated by the nearest float
+fn number_value(value: NumberValue) -> Value {
+    match value {
+        NumberValue::UnsignedInteger(value) => {
+            u64::try_from(value).map_or_else(|_| json!(value as f64), |value| json!(value))
+        }
+        NumberValue::SignedInteger(value) => {
+            i64::try_from(value).map_or_else(|_| json!(value as f64), |value| json!(value))
+        }
+        NumberValue::Float(value) => json!(value),
+    }
+}
+
+fn number_schema(ty: NumberType, gui: &NumericGuiType) -> Map<String, Value> {
+    let mut schema = Map::new();
+
+    let ty_str = match ty {
+        NumberType::UnsignedInteger { .. } | NumberType::SignedInteger { .. } => "integer",
+        NumberType::Float => "number",
+    };
+    schema.insert("type".into(), json!(ty_str));
+
+    // The slider range is contained in the range of the integer type
+    let range = if let NumericGuiType::Slider { range, .. } = gui {
+        Some(range.clone())
+    } else {
+        ty.integer_range()
+    };
+    if let Some(range) = range {
+        schema.insert("minimum".into(), number_value(*range.start()));
+        schema.insert("maximum".into(), number_value(*range.end()));
+    }
+
+    schema
+}
+
+// Strings and flags have no JSON Schema equivalent. They are carried as annotations, using the `x-`
+// prefix for custom keywords. The `help` string is also used as description.
+fn add_strings_annotations(schema: &mut Value, strings: &HashMap<String, String>) {
+    if let Value::Object(schema) = schema {
+        if let Some(help) = strings.get("help") {
+            schema.insert("description".into(), json!(help));
+        }
+        if !strings.is_empty() {
+            schema.insert("x-strings".into(), json!(strings));
+        }
+    }
+}
+
+// The strings of an entry take precedence over the ones of the struct or enum it contains
+fn add_entry_annotations<T>(schema: &mut Value, entry: &SchemaEntry<T>) {
+    add_strings_annotations(schema, &entry.strings);
+    if let Value::Object(schema) = schema {
+        if !entry.flags.is_empty() {
+            let mut flags = entry.flags.iter().collect::<Vec<_>>();
+            flags.sort();
+            schema.insert("x-flags".into(), json!(flags));
+        }
+    }
+}
+
+fn node_schema(node: &SchemaNode) -> Value {
+    match node {
+        SchemaNode::Section {
+            entries,
+            strings,
+            skipped,
+            ..
+        } => {
+            let mut properties = Map::new();
+            for entry in entries {
+                let mut schema = node_schema(&entry.content);
+                add_entry_annotations(&mut schema, entry);
+                properties.insert(entry.name.clone(), schema);
+            }
+            // The values of skipped fields are not described, and they are not required
+            for name in skipped {
+                properties.insert(name.clone(), json!(true));
+            }
+            let required = entries.iter().map(|entry| &entry.name).collect::<Vec<_>>();
+
+            let mut schema = json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+                "additionalProperties": false,
+            });
+            add_strings_annotations(&mut schema, strings);
+
+            schema
+        }
+        // Enums are externally tagged: unit variants are strings, the others are objects with a
+        // single key
+        SchemaNode::Choice {
+            default,
+            variants,
+            strings,
+            skipped,
+            ..
+        } => {
+            let mut one_of = variants
+                .iter()
+                .map(|entry| {
+                    let mut schema = if let Some(content) = &entry.content {
+                        json!({
+                            "type": "object",
+                            "properties": { entry.name.clone(): node_schema(content) },
+                            "required": [entry.name],
+                            "additionalProperties": false,
+                        })
+                    } else {
+                        json!({ "const": entry.name })
+                    };
+                    add_entry_annotations(&mut schema, entry);
+
+                    schema
+                })
+                .collect::<Vec<_>>();
+            // The content of skipped variants is not described, so they can be unit variants or
+            // have any content
+            for name in skipped {
+                one_of.push(json!({ "const": name }));
+                one_of.push(json!({
+                    "type": "object",
+                    "properties": { name.clone(): true },
+                    "required": [name],
+                    "additionalProperties": false,
+                }));
+            }
+
+            let mut schema = json!({ "oneOf": one_of });
+            let default_is_unit = variants
+                .iter()
+                .any(|entry| entry.name == *default && entry.content.is_none());
+            if default_is_unit {
+                schema["default"] = json!(default);
+            }
+            add_strings_annotations(&mut schema, strings);
+
+            schema
+        }
+        SchemaNode::Optional { content, .. } => {
+            json!({ "anyOf": [node_schema(content), { "type": "null" }] })
+        }
+        SchemaNode::Switch { content, .. } => json!({
+            "oneOf": [
+                {
+                    "type": "object",
+                    "properties": { "Enabled": node_schema(content) },
+                    "required": ["Enabled"],
+                    "additionalProperties": false,
+                },
+                { "const": "Disabled" },
+            ]
+        }),
+        SchemaNode::Boolean { default } => json!({ "type": "boolean", "default": default }),
+        SchemaNode::Number {
+            default, ty, gui, ..
+        } => {
+            let mut schema = number_schema(*ty, gui);
+            schema.insert("default".into(), number_value(*default));
+
+            Value::Object(schema)
+        }
+        SchemaNode::Range {
+            default, ty, gui, ..
+        } => {
+            let bound_schema = number_schema(*ty, gui);
+
+            json!({
+                "type": "object",
+                "properties": {
+                    "start": bound_schema,
+                    "end": bound_schema,
+                },
+                "required": ["start", "end"],
+                "additionalProperties": false,
+                "default": {
+                    "start": number_value(default[0]),
+                    "end": number_value(default[1]),
+                },
+            })
+        }
+        SchemaNode::Percentage {
+            default_normalized,
+            range_normalized,
+        } => {
+            let mut schema = json!({ "type": "number", "default": default_normalized });
+            if let Some(range) = range_normalized {
+                schema["minimum"] = json!(range.start());
+                schema["maximum"] = json!(range.end());
+            }
+
+            schema
+        }
+        // std::time::Duration is serialized by serde as seconds plus nanoseconds
+        SchemaNode::Duration { default, unit, .. } => {
+            let mut schema = json!({
+                "type": "object",
+                "properties": {
+                    "secs": { "type": "integer", "minimum": 0 },
+                    "nanos": { "type": "integer", "minimum": 0, "maximum": 999_999_999 },
+                },
+                "required": ["secs", "nanos"],
+                "additionalProperties": false,
+                "x-unit": unit,
+            });
+            // Deserialized schemas can contain defaults that are negative, not finite or too big
+            if let Ok(default) = Duration::try_from_secs_f64(default * unit.as_secs_f64()) {
+                schema["default"] =
+                    json!({ "secs": default.as_secs(), "nanos": default.subsec_nanos() });
+            }
+
+            schema
+        }
+        SchemaNode::Text { default } => json!({ "type": "string", "default": default }),
+        SchemaNode::Array(elements) => json!({
+            "type": "array",
+            "prefixItems": elements.iter().map(node_schema).collect::<Vec<_>>(),
+            "items": false,
+            "minItems": elements.len(),
+            "maxItems": elements.len(),
+        }),
+        SchemaNode::Tuple { entries, strings } => {
+            let items = entries
+                .iter()
+                .map(|entry| {
+                    let mut schema = node_schema(&entry.content);
+                    add_entry_annotations(&mut schema, entry);
+
+                    schema
+                })
+                .collect::<Vec<_>>();
+
+            let mut schema = json!({
+                "type": "array",
+                "prefixItems": items,
+                "items": false,
+                "minItems": entries.len(),
+                "maxItems": entries.len(),
+            });
+            add_strings_annotations(&mut schema, strings);
+
+            schema
+        }
+        SchemaNode::Vector {
+            default_element,
+            default,
+        } => json!({
+            "type": "array",
+            "items": node_schema(default_element),
+            "default": default,
+        }),
+        SchemaNode::Set {
+            default_element,
+            default,
+            ..
+        } => json!({
+            "type": "array",
+            "items": node_schema(default_element),
+            "uniqueItems": true,
+            "default": default,
+        }),
+        SchemaNode::Dictionary {
+            key,
+            default_value,
+            default,
+            ordered,
+            map: true,
+            ..
+        } => json!({
+            "type": "object",
+            "propertyNames": property_names_schema(key.as_deref()),
+            "additionalProperties": node_schema(default_value),
+            "default": default.iter().cloned().collect::<Map<_, _>>(),
+            "x-ordered": ordered,
+        }),
+        SchemaNode::Dictionary {
+            key,
+            default_value,
+            default,
+            ordered,
+            ..
+        } => json!({
+            "type": "array",
+            "items": {
+                "type": "array",
+                "prefixItems": [
+                    key.as_deref().map_or_else(|| json!({ "type": "string" }), node_schema),
+                    node_schema(default_value),
+                ],
+                "items": false,
+                "minItems": 2,
+                "maxItems": 2,
+            },
+            "default": default,
+            "x-ordered": ordered,
+        }),
+        SchemaNode::Reference(name) => json!({ "$ref": definition_ref(name) }),
+    }
+}
+
+// URI fragment containing the JSON pointer to a definition. Type names can contain characters
+// like `<`, `>` and spaces, that are percent-encoded
+fn definition_ref(name: &str) -> String {
+    let token = name.replace('~', "~0").replace('/', "~1");
+
+    let mut reference = String::from("#/$defs/");
+    for byte in token.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~:@!$&'()*+,;=".contains(&byte) {
+            reference.push(byte as char);
+        } else {
+            reference.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    reference
+}
+
+// Map keys are serialized as strings, so only their format can be described
+fn property_names_schema(key: Option<&SchemaNode>) -> Value {
+    match key {
+        Some(SchemaNode::Number {
+            ty: NumberType::UnsignedInteger { .. },
+            ..
+        }) => json!({ "pattern": "^[0-9]+$" }),
+        Some(SchemaNode::Number {
+            ty: NumberType::SignedInteger { .. },
+            ..
+        }) => json!({ "pattern": "^-?[0-9]+$" }),
+        Some(SchemaNode::Number {
+            ty: NumberType::Float,
+            ..
+        }) => json!({ "pattern": "^-?[0-9]+(\\.[0-9]+)?([eE][+-]?[0-9]+)?$" }),
+        Some(SchemaNode::Choice { variants, .. }) => json!({
+            "enum": variants
+                .iter()
+                .filter(|variant| variant.content.is_none())
+                .map(|variant| &variant.name)
+                .collect::<Vec<_>>(),
+        }),
+        _ => json!({ "type": "string" }),
+    }
+}
+
+impl SchemaNode {
+    /// Convert the schema into a JSON Schema (draft 2020-12) document, describing the JSON form of
+    /// the settings (not of the `*Default` representation). Entry strings and flags are exported
+    /// as `x-strings` and `x-flags` annotations.
+    /// Schemas containing `SchemaNode::Reference` nodes must be converted with
+    /// `to_json_schema_with_definitions()`.
+    pub fn to_json_schema(&self) -> Value {
+        self.to_json_schema_with_definitions(&SchemaDefinitions::new())
+    }
+
+    /// Like `to_json_schema()`, adding `definitions` (usually obtained with
+    /// `SettingsSchema::schema_definitions()`) to the `$defs` of the document, so that
+    /// `SchemaNode::Reference` nodes can be resolved.
+    pub fn to_json_schema_with_definitions(&self, definitions: &SchemaDefinitions) -> Value {
+        let mut schema = node_schema(self);
+        if let Value::Object(schema) = &mut schema {
+            schema.insert("$schema".into(), json!(JSON_SCHEMA_DIALECT));
+            if !definitions.is_empty() {
+                let definitions = definitions
+                    .iter()
+                    .map(|(name, node)| (name.clone(), node_schema(node)))
+                    .collect::<Map<_, _>>();
+                schema.insert("$defs".into(), Value::Object(definitions));
+            }
+        }
+
+        schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DurationUnit, SchemaEntry};
+
+    fn entry<T>(name: &str, content: T) -> SchemaEntry<T> {
+        SchemaEntry {
+            name: name.into(),
+            strings: Default::default(),
+            flags: Default::default(),
+            content,
+        }
+    }
+
+    fn number(ty: NumberType) -> SchemaNode {
+        SchemaNode::Number {
+            default: 0.into(),
+            ty,
+            gui: NumericGuiType::TextBox,
+            suffix: None,
+        }
+    }
+
+    fn map(key: Option<SchemaNode>) -> SchemaNode {
+        SchemaNode::Dictionary {
+            default_key: "".into(),
+            key: key.map(Box::new),
+            default_value: Box::new(SchemaNode::Boolean { default: false }),
+            default: vec![],
+            ordered: false,
+            map: true,
+        }
+    }
+
+    #[test]
+    fn document() {
+        let node = SchemaNode::Boolean { default: true };
+        let mut definitions = SchemaDefinitions::new();
+        definitions.insert("a::B".into(), SchemaNode::Boolean { default: false });
+
+        assert_eq!(
+            node.to_json_schema(),
+            json!({
+                "$schema": JSON_SCHEMA_DIALECT,
+                "type": "boolean",
+                "default": true,
+            })
+        );
+        assert_eq!(
+            node.to_json_schema_with_definitions(&definitions),
+            json!({
+                "$schema": JSON_SCHEMA_DIALECT,
+                "type": "boolean",
+                "default": true,
+                "$defs": { "a::B": { "type": "boolean", "default": false } },
+            })
+        );
+    }
+
+    #[test]
+    fn section() {
+        let mut field = entry("a", SchemaNode::Text { default: "".into() });
+        field.strings.insert("help".into(), "Help".into());
+        field.flags.insert("steamvr-restart".into());
+        let node = SchemaNode::Section {
+            entries: vec![field],
+            gui_collapsible: false,
+            strings: Default::default(),
+            skipped: vec!["b".into()],
+        };
+
+        assert_eq!(
+            node_schema(&node),
+            json!({
+                "type": "object",
+                "properties": {
+                    "a": {
+                        "type": "string",
+                        "default": "",
+                        "description": "Help",
+                        "x-strings": { "help": "Help" },
+                        "x-flags": ["steamvr-restart"],
+                    },
+                    "b": true,
+                },
+                "required": ["a"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn choice() {
+        let node = SchemaNode::Choice {
+            default: "A".into(),
+            variants: vec![
+                entry("A", None),
+                entry("B", Some(number(NumberType::Float))),
+            ],
+            gui: None,
+            strings: Default::default(),
+            skipped: vec!["C".into()],
+        };
+
+        assert_eq!(
+            node_schema(&node),
+            json!({
+                "oneOf": [
+                    { "const": "A" },
+                    {
+                        "type": "object",
+                        "properties": { "B": { "type": "number", "default": 0 } },
+                        "required": ["B"],
+                        "additionalProperties": false,
+                    },
+                    { "const": "C" },
+                    {
+                        "type": "object",
+                        "properties": { "C": true },
+                        "required": ["C"],
+                        "additionalProperties": false,
+                    },
+                ],
+                "default": "A",
+            })
+        );
+    }
+
+    #[test]
+    fn tuple() {
+        let node = SchemaNode::Tuple {
+            entries: vec![entry("0", SchemaNode::Boolean { default: false })],
+            strings: [("help".into(), "Help".into())].into(),
+        };
+
+        assert_eq!(
+            node_schema(&node),
+            json!({
+                "type": "array",
+                "prefixItems": [{ "type": "boolean", "default": false }],
+                "items": false,
+                "minItems": 1,
+                "maxItems": 1,
+                "description": "Help",
+                "x-strings": { "help": "Help" },
+            })
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            node_schema(&number(NumberType::SignedInteger { bits: 8 })),
+            json!({ "type": "integer", "minimum": -128, "maximum": 127, "default": 0 })
+        );
+        // Integers wider than 64 bits are approximated
+        assert_eq!(
+            node_schema(&number(NumberType::UnsignedInteger { bits: 128 })),
+            json!({
+                "type": "integer",
+                "minimum": 0,
+                "maximum": u128::MAX as f64,
+                "default": 0,
+            })
+        );
+
+        let node = SchemaNode::Range {
+            default: [1.into(), 2.into()],
+            ty: NumberType::UnsignedInteger { bits: 8 },
+            gui: NumericGuiType::Slider {
+                range: 0.into()..=10.into(),
+                step: None,
+                logarithmic: false,
+            },
+            suffix: None,
+        };
+        let bound = json!({ "type": "integer", "minimum": 0, "maximum": 10 });
+        assert_eq!(
+            node_schema(&node),
+            json!({
+                "type": "object",
+                "properties": { "start": bound, "end": bound },
+                "required": ["start", "end"],
+                "additionalProperties": false,
+                "default": { "start": 1, "end": 2 },
+            })
+        );
+    }
+
+    #[test]
+    fn duration() {
+        let node = |default| SchemaNode::Duration {
+            default,
+            unit: DurationUnit::Milliseconds,
+            gui: NumericGuiType::TextBox,
+        };
+
+        let schema = node_schema(&node(1500.0));
+        assert_eq!(schema["type"], json!("object"));
+        assert_eq!(schema["required"], json!(["secs", "nanos"]));
+        assert_eq!(schema["x-unit"], json!("Milliseconds"));
+        assert_eq!(
+            schema["default"],
+            json!({ "secs": 1, "nanos": 500_000_000 })
+        );
+
+        assert!(node_schema(&node(-1.0)).get("default").is_none());
+        assert!(node_schema(&node(f64::NAN)).get("default").is_none());
+    }
+
+    #[test]
+    fn map_property_names() {
+        let pattern = |key| node_schema(&map(key))["propertyNames"].clone();
+
+        assert_eq!(
+            pattern(Some(number(NumberType::UnsignedInteger { bits: 32 }))),
+            json!({ "pattern": "^[0-9]+$" })
+        );
+        assert_eq!(
+            pattern(Some(number(NumberType::SignedInteger { bits: 32 }))),
+            json!({ "pattern": "^-?[0-9]+$" })
+        );
+        assert_eq!(
+            pattern(Some(number(NumberType::Float))),
+            json!({ "pattern": "^-?[0-9]+(\\.[0-9]+)?([eE][+-]?[0-9]+)?$" })
+        );
+        assert_eq!(pattern(None), json!({ "type": "string" }));
+    }
+
+    #[test]
+    fn definition_refs() {
+        assert_eq!(definition_ref("a::B"), "#/$defs/a::B");
+        assert_eq!(
+            definition_ref("a::B<u8, c::D>"),
+            "#/$defs/a::B%3Cu8,%20c::D%3E"
+        );
+        assert_eq!(definition_ref("a/b~c"), "#/$defs/a~1b~0c");
+        assert_eq!(
+            node_schema(&SchemaNode::Reference("A<B>".into())),
+            json!({ "$ref": "#/$defs/A%3CB%3E" })
+        );
+    }
+}
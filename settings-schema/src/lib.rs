@@ -1,52 +1,210 @@
+mod impls;
+mod json_schema;
+mod number;
+mod validate;
+
+use number::ExactIntegers;
+use serde::{
+    de::{DeserializeOwned, MapAccess, SeqAccess, Visitor},
+    Deserializer,
+};
 use std::{
-    collections::{HashMap, HashSet},
-    ops::RangeInclusive,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Debug, Display, Formatter},
+    marker::PhantomData,
+    ops::{Deref, RangeInclusive},
+    time::Duration,
 };
 
+pub use number::{NumberType, NumberValue};
 pub use settings_schema_derive::SettingsSchema;
+pub use validate::{ValidationError, ValidationErrorKind};
 
 // For the derive macro
 pub use serde::{Deserialize, Serialize};
 pub use serde_json::to_value as to_json_value;
 
-// #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
-// pub struct Percentage(f32);
-
-// impl Percentage {
-//     pub fn new(value: u8) -> Self {
-//         Self(value as f32 / 100.0)
-//     }
-
-//     pub fn new_normalized(value: f32) -> Self {
-//         Self(value)
-//     }
-// }
-
-// impl From<u8> for Percentage {
-//     fn from(value: u8) -> Self {
-//         Self(value as f32)
-//     }
-// }
-
-// impl From<f32> for Percentage {
-//     fn from(value: f32) -> Self {
-//         Self(value)
-//     }
-// }
-
-// impl Deref for Percentage {
-//     type Target = f32;
-
-//     fn deref(&self) -> &f32 {
-//         &self.0
-//     }
-// }
-
-// impl Display for Percentage {
-//     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-//         write!(f, "{}%", (self.0 * 100.0) as u8)
-//     }
-// }
+// Names of the entries of a struct, including the ones of flattened fields. Used by the derive
+// macro to detect name collisions at compile time
+#[doc(hidden)]
+pub struct EntryNames {
+    pub names: &'static [&'static str],
+    pub flattened: &'static [&'static EntryNames],
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+impl EntryNames {
+    const fn count(&self) -> usize {
+        let mut count = self.names.len();
+        let mut i = 0;
+        while i < self.flattened.len() {
+            count += self.flattened[i].count();
+            i += 1;
+        }
+
+        count
+    }
+
+    const fn get(&self, index: usize) -> &'static str {
+        if index < self.names.len() {
+            return self.names[index];
+        }
+
+        let mut index = index - self.names.len();
+        let mut i = 0;
+        loop {
+            let count = self.flattened[i].count();
+            if index < count {
+                return self.flattened[i].get(index);
+            }
+            index -= count;
+            i += 1;
+        }
+    }
+
+    pub const fn has_duplicates(&self) -> bool {
+        let count = self.count();
+        let mut i = 0;
+        while i < count {
+            let mut j = i + 1;
+            while j < count {
+                if str_eq(self.get(i), self.get(j)) {
+                    return true;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+
+        false
+    }
+}
+
+// Number type of the primitive number types. Used by the derive macro for types marked with
+// `as = "number"`, which are usually aliases of primitive number types
+#[doc(hidden)]
+pub trait Number {
+    const TYPE: NumberType;
+}
+
+// Serialized form of a default value stored as JSON in the schema. Integers that are not
+// represented exactly by `f64` are written as decimal strings, like `NumberValue` does
+#[doc(hidden)]
+pub fn default_json_value<T: Serialize + ?Sized>(value: &T) -> serde_json::Value {
+    serde_json::to_value(ExactIntegers(value)).unwrap()
+}
+
+// Serialized form of a map key: the string used as JSON object key
+#[doc(hidden)]
+pub fn map_key_string<K: Serialize>(key: &K) -> String {
+    match default_json_value(key) {
+        serde_json::Value::String(key) => key,
+        key => key.to_string(),
+    }
+}
+
+// Schema of the keys of a dictionary node. Text keys are fully described by `default_key`
+#[doc(hidden)]
+pub fn dictionary_key_schema(key: SchemaNode) -> Option<Box<SchemaNode>> {
+    match key {
+        SchemaNode::Text { .. } => None,
+        key => Some(Box::new(key)),
+    }
+}
+
+// Default content of a dictionary node
+#[doc(hidden)]
+pub fn dictionary_content<K: Serialize, T: Serialize>(
+    content: &[(K, T)],
+) -> Vec<(String, serde_json::Value)> {
+    content
+        .iter()
+        .map(|(key, value)| (map_key_string(key), default_json_value(value)))
+        .collect()
+}
+
+// Options of a set node, for elements of unit-only enum type
+#[doc(hidden)]
+pub fn set_options(default_element: &SchemaNode) -> Option<Vec<SchemaEntry<()>>> {
+    if let SchemaNode::Choice { variants, .. } = default_element {
+        if variants.iter().all(|variant| variant.content.is_none()) {
+            return Some(
+                variants
+                    .iter()
+                    .map(|variant| SchemaEntry {
+                        name: variant.name.clone(),
+                        strings: variant.strings.clone(),
+                        flags: variant.flags.clone(),
+                        content: (),
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    None
+}
+
+/// Percentage value. It is stored and serialized in normalized form, where 1.0 corresponds to 100%.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Percentage(f32);
+
+impl Percentage {
+    pub fn new(value: u8) -> Self {
+        Self(value as f32 / 100.0)
+    }
+
+    pub fn new_normalized(value: f32) -> Self {
+        Self(value)
+    }
+
+    pub fn normalized(self) -> f32 {
+        self.0
+    }
+}
+
+impl From<u8> for Percentage {
+    fn from(value: u8) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<f32> for Percentage {
+    fn from(value: f32) -> Self {
+        Self::new_normalized(value)
+    }
+}
+
+impl Deref for Percentage {
+    type Target = f32;
+
+    fn deref(&self) -> &f32 {
+        &self.0
+    }
+}
+
+impl Display for Percentage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.0}%", self.0 * 100.0)
+    }
+}
 
 /// The `Switch` is used to represent something that makes sense to specify its state only when it's enabled.
 /// This should be used differently than `Option(al)`, that represent a value that can be omitted.
@@ -78,7 +236,7 @@ impl<T> Switch<T> {
 
 /// Type used to specify the default value for type `Option`.  
 /// It allows specifying the set state and its content when it is set.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct OptionalDefault<C> {
     pub set: bool,
     pub content: C,
@@ -86,7 +244,7 @@ pub struct OptionalDefault<C> {
 
 /// Type used to specify the default value for type `Switch`.
 /// It allows setting the enabled state and its content when set to enabled.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct SwitchDefault<C> {
     pub enabled: bool,
     pub content: C,
@@ -99,41 +257,210 @@ pub struct ArrayDefault<T> {
     pub content: T,
 }
 
+impl<T: Default, const N: usize> Default for ArrayDefault<[T; N]> {
+    fn default() -> Self {
+        Self {
+            gui_collapsed: false,
+            content: [(); N].map(|_| T::default()),
+        }
+    }
+}
+
 /// Type used to specify the default value for type `Vec`.
 /// It allows setting the default for the vector (all elements) and the default value for new elements.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct VectorDefault<T> {
     pub gui_collapsed: bool,
     pub element: T,
     pub content: Vec<T>,
 }
 
-/// Type used to specify the default value for type `Vec<(String, X)>`.
+/// Type used to specify the default value for type `RangeInclusive`.
+/// `start` must not be greater than `end`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RangeDefault<T> {
+    pub start: T,
+    pub end: T,
+}
+
+/// Type used to specify the default value for dictionary types (`Vec<(String, X)>` and maps).
 /// It allows setting the default for the dictionary (all entries) and the default key and value for new entries.
+/// `content` can be deserialized both from a list of `[key, value]` pairs and from a map.
+/// Keys are stored as they are (`K` is the key type of the dictionary), or as `String` for string
+/// keys.
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct DictionaryDefault<T> {
+pub struct DictionaryDefault<T, K = String> {
     pub gui_collapsed: bool,
-    pub key: String,
+    pub key: K,
     pub value: T,
-    pub content: Vec<(String, T)>,
+    #[serde(deserialize_with = "deserialize_dictionary_content")]
+    pub content: Vec<(K, T)>,
+}
+
+// The default key is the default value of the key type, that is not required to implement `Default`
+impl<T: Default, K: SettingsSchema> Default for DictionaryDefault<T, K> {
+    fn default() -> Self {
+        Self {
+            gui_collapsed: false,
+            key: K::from_default(Default::default()),
+            value: T::default(),
+            content: vec![],
+        }
+    }
+}
+
+fn deserialize_dictionary_content<'de, D, K, T>(deserializer: D) -> Result<Vec<(K, T)>, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de>,
+    T: Deserialize<'de>,
+{
+    struct ContentVisitor<K, T>(PhantomData<(K, T)>);
+
+    impl<'de, K: Deserialize<'de>, T: Deserialize<'de>> Visitor<'de> for ContentVisitor<K, T> {
+        type Value = Vec<(K, T)>;
+
+        fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+            f.write_str("a list of [key, value] pairs or a map")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut content = vec![];
+            while let Some(entry) = seq.next_element()? {
+                content.push(entry);
+            }
+
+            Ok(content)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut content = vec![];
+            while let Some(entry) = map.next_entry()? {
+                content.push(entry);
+            }
+
+            Ok(content)
+        }
+    }
+
+    deserializer.deserialize_any(ContentVisitor(PhantomData))
+}
+
+/// Type used to specify the default value for recursive fields (fields whose type contains the type
+/// being defined) and for fields marked with `reference`. The default value is stored only when it
+/// is set explicitly, so that the default representation of recursive types stays finite. When it
+/// is not set, the field uses the default values of `T` and its schema is a reference to the
+/// definition of `T`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(transparent)]
+pub struct ReferenceDefault<T>(pub Option<Box<T>>);
+
+impl<T> ReferenceDefault<T> {
+    pub fn new(default: T) -> Self {
+        Self(Some(Box::new(default)))
+    }
+
+    pub fn into_inner(self) -> T
+    where
+        T: Default,
+    {
+        self.0.map(|default| *default).unwrap_or_default()
+    }
+}
+
+impl<T> Default for ReferenceDefault<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+/// Definitions of the named types referenced by `SchemaNode::Reference`, by name.
+pub type SchemaDefinitions = BTreeMap<String, SchemaNode>;
+
+// Schema of a `ReferenceDefault` field. Explicit defaults are inlined, since they can differ from
+// the ones in the definition of the type
+#[doc(hidden)]
+pub fn reference_schema<T: SettingsSchema>(default: ReferenceDefault<T::Default>) -> SchemaNode {
+    match (default.0, T::schema_name()) {
+        (Some(default), _) => T::schema(*default),
+        (None, Some(name)) => SchemaNode::Reference(name),
+        (None, None) => T::schema(Default::default()),
+    }
+}
+
+// Schema of a type marked with `#[schema(reference)]`. Like for `ReferenceDefault` fields, defaults
+// that differ from the ones in the definition of the type are inlined
+#[doc(hidden)]
+pub fn referenced_schema<T: SettingsSchema>(default: T::Default) -> SchemaNode {
+    match T::schema_name() {
+        Some(name)
+            if default_json_value(&default) == default_json_value(&T::Default::default()) =>
+        {
+            SchemaNode::Reference(name)
+        }
+        _ => T::definition_schema(default),
+    }
+}
+
+// Name of a type argument in the schema name of a generic type. Types that implement
+// `SettingsSchema` manually without a name fall back to the compiler type name
+#[doc(hidden)]
+pub fn schema_name_of<T: SettingsSchema>() -> String {
+    T::schema_name().unwrap_or_else(|| std::any::type_name::<T>().into())
+}
+
+// Add the definition of a type referenced by a `ReferenceDefault` field. The entry is reserved
+// before visiting the fields of the type, to stop at recursive references
+#[doc(hidden)]
+pub fn add_definition<T: SettingsSchema>(definitions: &mut SchemaDefinitions) {
+    if let Some(name) = T::schema_name() {
+        if !definitions.contains_key(&name) {
+            definitions.insert(name.clone(), SchemaNode::Reference(name.clone()));
+            T::definitions(definitions);
+            definitions.insert(name, T::definition_schema(Default::default()));
+        }
+    } else {
+        T::definitions(definitions);
+    }
 }
 
 /// GUI type associated to a numeric node.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum NumericGuiType {
     Slider {
-        range: RangeInclusive<f64>,
-        step: Option<f64>,
+        range: RangeInclusive<NumberValue>,
+        step: Option<NumberValue>,
         logarithmic: bool,
     },
     TextBox,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
-pub enum NumberType {
-    UnsignedInteger,
-    SignedInteger,
-    Float,
+/// Unit used to display a duration node. Default value and slider range are expressed in this unit.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DurationUnit {
+    Milliseconds,
+    Seconds,
+    Minutes,
+}
+
+impl DurationUnit {
+    pub fn as_secs_f64(self) -> f64 {
+        match self {
+            Self::Milliseconds => 0.001,
+            Self::Seconds => 1.0,
+            Self::Minutes => 60.0,
+        }
+    }
+
+    /// Express `duration` as a (fractional) number of this unit.
+    pub fn count(self, duration: Duration) -> f64 {
+        duration.as_secs_f64() / self.as_secs_f64()
+    }
+
+    /// Inverse of `count()`.
+    pub fn duration(self, count: f64) -> Duration {
+        Duration::from_secs_f64(count * self.as_secs_f64())
+    }
 }
 
 /// GUI type associated to the choice node.
@@ -157,14 +484,25 @@ pub struct SchemaEntry<T> {
 #[non_exhaustive]
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum SchemaNode {
+    // `strings` are the strings of the struct or enum itself, like the `help` from its doc comment.
+    // `skipped` contains the names of the fields and variants that are skipped by the schema but
+    // are still serialized. Their values are not described, and they are accepted by the validation
     Section {
         entries: Vec<SchemaEntry<SchemaNode>>,
         gui_collapsible: bool,
+        #[serde(default)]
+        strings: HashMap<String, String>,
+        #[serde(default)]
+        skipped: Vec<String>,
     },
     Choice {
         default: String,
         variants: Vec<SchemaEntry<Option<SchemaNode>>>,
         gui: Option<ChoiceControlType>,
+        #[serde(default)]
+        strings: HashMap<String, String>,
+        #[serde(default)]
+        skipped: Vec<String>,
     },
     Optional {
         default_set: bool,
@@ -178,37 +516,139 @@ pub enum SchemaNode {
         default: bool,
     },
     Number {
-        default: f64,
+        default: NumberValue,
         ty: NumberType,
         gui: NumericGuiType,
         suffix: Option<String>,
     },
-    // Range {
-    //     default: [f64; 2],
-    //     ty: NumberType,
-    //     gui: NumericGuiType,
-    //     suffix: Option<String>,
-    // },
-    // Percentage {
-    //     default_normalized: f32,
-    //     range_normalized: Option<RangeInclusive<f32>>,
-    // },
-    // Duration {
-    //     default: Duration,
-    //     range: RangeInclusive<Duration>,
-    //     logarithmic: bool,
-    // },
+    Range {
+        default: [NumberValue; 2],
+        ty: NumberType,
+        gui: NumericGuiType,
+        suffix: Option<String>,
+    },
+    Percentage {
+        default_normalized: f32,
+        range_normalized: Option<RangeInclusive<f32>>,
+    },
+    Duration {
+        default: f64,
+        unit: DurationUnit,
+        gui: NumericGuiType,
+    },
     Text {
         default: String,
     },
     Array(Vec<SchemaNode>),
+    // Tuple struct. Entries are named after their position. `strings` are the strings of the struct
+    // itself, like for Section
+    Tuple {
+        entries: Vec<SchemaEntry<SchemaNode>>,
+        #[serde(default)]
+        strings: HashMap<String, String>,
+    },
     Vector {
         default_element: Box<SchemaNode>,
         default: Vec<serde_json::Value>,
     },
+    // `HashSet`, `BTreeSet` and `Vec` with `multi_select`. Elements must be unique. If the element
+    // is a unit-only enum, `options` lists its variants, so that GUIs can show a group of
+    // checkboxes
+    Set {
+        default_element: Box<SchemaNode>,
+        options: Option<Vec<SchemaEntry<()>>>,
+        default: Vec<serde_json::Value>,
+    },
+    // `Vec<(String, X)>` and maps. `default_key` is the key of new entries. Keys that are not
+    // strings (only in maps) are described by `key` (a Number or unit-only Choice node), and they
+    // are stored in `default_key` and `default` in their serialized form, the string used as JSON
+    // object key. `ordered` tells if the order of the entries is meaningful (it is not for
+    // `HashMap` and `BTreeMap`), and `map` if the dictionary is serialized as a map instead of a
+    // list of [key, value] pairs
     Dictionary {
         default_key: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key: Option<Box<SchemaNode>>,
         default_value: Box<SchemaNode>,
         default: Vec<(String, serde_json::Value)>,
+        #[serde(default = "default_true")]
+        ordered: bool,
+        #[serde(default)]
+        map: bool,
     },
+    // Named type defined in the `SchemaDefinitions` of the root type. Used for recursive fields and
+    // fields marked with `reference`
+    Reference(String),
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Types that can be described by a schema. It is implemented by `#[derive(SettingsSchema)]`, and
+/// it can be implemented manually for types that cannot use the derive macro. Type parameters of
+/// derived types must implement this trait.
+pub trait SettingsSchema: Sized {
+    /// Default representation of the type (`<YourStructOrEnum>Default` for derived types). It
+    /// contains the default values of the type, including the ones for inactive variants and for
+    /// new elements of collections.
+    type Default: Serialize + DeserializeOwned + Clone + Debug + std::default::Default;
+
+    /// Create the schema of the type, with `default` as default values.
+    fn schema(default: Self::Default) -> SchemaNode;
+
+    /// Create a value from its default representation, choosing the default variants and states.
+    fn from_default(default: Self::Default) -> Self;
+
+    /// Write the value into a previous default representation (or session), keeping the data of
+    /// the inactive variants and the GUI state.
+    fn default_from_value(default: Self::Default, value: &Self) -> Self::Default;
+
+    /// Name of the type in the schema definitions, used by `SchemaNode::Reference`. Derived types
+    /// are named after their module path and the names of the type arguments that affect the
+    /// schema (like `my_crate::Tree<u8>`), the runtime implementations after their type. Types
+    /// without a name are never referenced.
+    fn schema_name() -> Option<String> {
+        None
+    }
+
+    /// Schema of the type in the schema definitions, with `default` as default values. It differs
+    /// from `schema()` for derived types marked with `#[schema(reference)]`, whose schema is a
+    /// reference to this definition.
+    fn definition_schema(default: Self::Default) -> SchemaNode {
+        Self::schema(default)
+    }
+
+    /// Add the definitions of the named types referenced in the schema of this type.
+    fn definitions(_definitions: &mut SchemaDefinitions) {}
+
+    /// Definitions of the named types referenced in the schema of this type, needed to resolve its
+    /// `SchemaNode::Reference` nodes.
+    fn schema_definitions() -> SchemaDefinitions {
+        let mut definitions = SchemaDefinitions::new();
+        Self::definitions(&mut definitions);
+
+        definitions
+    }
+}
+
+/// Schema of a type from another crate, that cannot implement `SettingsSchema` because of the
+/// orphan rules. It is implemented by `#[derive(SettingsSchema)]` on a mirror definition of the
+/// type `T`, marked with `#[schema(remote = "path::to::T")]`. Fields of type `T` select the mirror
+/// with `#[schema(with = "Mirror")]`.
+pub trait RemoteSettingsSchema<T> {
+    /// Default representation of `T` (`<Mirror>Default` for derived mirrors).
+    type Default: Serialize + DeserializeOwned + Clone + Debug + std::default::Default;
+
+    /// Create the schema of `T`, with `default` as default values.
+    fn schema(default: Self::Default) -> SchemaNode;
+
+    /// Create a value of `T` from its default representation.
+    fn from_default(default: Self::Default) -> T;
+
+    /// Write the value into a previous default representation (or session).
+    fn default_from_value(default: Self::Default, value: &T) -> Self::Default;
+
+    /// Add the definitions of the named types referenced in the schema of `T`.
+    fn definitions(_definitions: &mut SchemaDefinitions) {}
 }
@@ -0,0 +1,594 @@
+use serde::{
+    de::{self, Visitor},
+    ser, Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Formatter},
+    ops::RangeInclusive,
+};
+
+// Integers with a greater magnitude are not represented exactly by `f64`, and so by the JSON
+// parsers of many languages
+const MAX_EXACT_INTEGER: u128 = 1 << 53;
+
+/// Type of a numeric node. Integer types carry their width in bits (`usize` and `isize` have the
+/// width of the target the schema was generated on).
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberType {
+    UnsignedInteger { bits: u32 },
+    SignedInteger { bits: u32 },
+    Float,
+}
+
+impl NumberType {
+    /// Range of the values representable by the type, `None` for floats.
+    pub fn integer_range(self) -> Option<RangeInclusive<NumberValue>> {
+        match self {
+            Self::UnsignedInteger { bits } => Some(
+                NumberValue::UnsignedInteger(0)
+                    ..=NumberValue::UnsignedInteger(u128::MAX >> (128 - bits)),
+            ),
+            Self::SignedInteger { bits } => {
+                let max = i128::MAX >> (128 - bits);
+                Some(NumberValue::SignedInteger(-max - 1)..=NumberValue::SignedInteger(max))
+            }
+            Self::Float => None,
+        }
+    }
+}
+
+// Schemas serialized before the introduction of the integer width contain only the name of the
+// number type. The integers are assumed to be 64 bit wide, like the number defaults of these
+// schemas
+impl<'de> Deserialize<'de> for NumberType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        enum Current {
+            UnsignedInteger { bits: u32 },
+            SignedInteger { bits: u32 },
+            Float,
+        }
+
+        #[derive(Deserialize)]
+        enum Legacy {
+            UnsignedInteger,
+            SignedInteger,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Current(Current),
+            Legacy(Legacy),
+        }
+
+        let ty = match Repr::deserialize(deserializer)? {
+            Repr::Current(Current::UnsignedInteger { bits })
+            | Repr::Current(Current::SignedInteger { bits })
+                if !(1..=128).contains(&bits) =>
+            {
+                return Err(de::Error::custom(format!(
+                    "invalid integer width of {} bits",
+                    bits
+                )))
+            }
+            Repr::Current(Current::UnsignedInteger { bits }) => Self::UnsignedInteger { bits },
+            Repr::Current(Current::SignedInteger { bits }) => Self::SignedInteger { bits },
+            Repr::Current(Current::Float) => Self::Float,
+            Repr::Legacy(Legacy::UnsignedInteger) => Self::UnsignedInteger { bits: 64 },
+            Repr::Legacy(Legacy::SignedInteger) => Self::SignedInteger { bits: 64 },
+        };
+
+        Ok(ty)
+    }
+}
+
+/// Value of a numeric node (default values, slider ranges and steps). Integers are stored exactly,
+/// including the 64 and 128 bit ones.
+///
+/// Values are compared numerically, regardless of the variant: integers are compared exactly,
+/// while comparisons with floats go through `f64`. When serialized, integers that are not exactly
+/// representable by `f64` (with a magnitude above 2^53) are written as decimal strings.
+#[derive(Clone, Copy, Debug)]
+pub enum NumberValue {
+    UnsignedInteger(u128),
+    SignedInteger(i128),
+    Float(f64),
+}
+
+impl NumberValue {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Self::UnsignedInteger(value) => value as f64,
+            Self::SignedInteger(value) => value as f64,
+            Self::Float(value) => value,
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($variant:ident, $inner_ty:ty, $($ty:ty),*) => {
+        $(
+            impl From<$ty> for NumberValue {
+                fn from(value: $ty) -> Self {
+                    Self::$variant(value as $inner_ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_from!(UnsignedInteger, u128, u8, u16, u32, u64, u128, usize);
+impl_from!(SignedInteger, i128, i8, i16, i32, i64, i128, isize);
+impl_from!(Float, f64, f32, f64);
+
+impl PartialEq for NumberValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for NumberValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (*self, *other) {
+            (Self::UnsignedInteger(a), Self::UnsignedInteger(b)) => a.partial_cmp(&b),
+            (Self::SignedInteger(a), Self::SignedInteger(b)) => a.partial_cmp(&b),
+            (Self::UnsignedInteger(a), Self::SignedInteger(b)) => {
+                if b < 0 {
+                    Some(Ordering::Greater)
+                } else {
+                    a.partial_cmp(&(b as u128))
+                }
+            }
+            (Self::SignedInteger(_), Self::UnsignedInteger(_)) => {
+                other.partial_cmp(self).map(Ordering::reverse)
+            }
+            (a, b) => a.as_f64().partial_cmp(&b.as_f64()),
+        }
+    }
+}
+
+impl Display for NumberValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsignedInteger(value) => write!(f, "{}", value),
+            Self::SignedInteger(value) => write!(f, "{}", value),
+            Self::Float(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Serialize for NumberValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Self::UnsignedInteger(value) if value <= MAX_EXACT_INTEGER => {
+                serializer.serialize_u64(value as u64)
+            }
+            Self::SignedInteger(value) if value.unsigned_abs() <= MAX_EXACT_INTEGER => {
+                serializer.serialize_i64(value as i64)
+            }
+            Self::UnsignedInteger(_) | Self::SignedInteger(_) => {
+                serializer.serialize_str(&self.to_string())
+            }
+            Self::Float(value) => serializer.serialize_f64(value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for NumberValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NumberValueVisitor;
+
+        impl<'de> Visitor<'de> for NumberValueVisitor {
+            type Value = NumberValue;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("a number or an integer as decimal string")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<NumberValue, E> {
+                Ok(NumberValue::UnsignedInteger(value as u128))
+            }
+
+            fn visit_u128<E: de::Error>(self, value: u128) -> Result<NumberValue, E> {
+                Ok(NumberValue::UnsignedInteger(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<NumberValue, E> {
+                self.visit_i128(value as i128)
+            }
+
+            fn visit_i128<E: de::Error>(self, value: i128) -> Result<NumberValue, E> {
+                if value < 0 {
+                    Ok(NumberValue::SignedInteger(value))
+                } else {
+                    Ok(NumberValue::UnsignedInteger(value as u128))
+                }
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<NumberValue, E> {
+                Ok(NumberValue::Float(value))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<NumberValue, E> {
+                if let Ok(value) = value.parse::<u128>() {
+                    Ok(NumberValue::UnsignedInteger(value))
+                } else if let Ok(value) = value.parse::<i128>() {
+                    Ok(NumberValue::SignedInteger(value))
+                } else {
+                    Err(E::invalid_value(de::Unexpected::Str(value), &self))
+                }
+            }
+        }
+
+        deserializer.deserialize_any(NumberValueVisitor)
+    }
+}
+
+// Serialize a value with its integers encoded like `NumberValue`, for the default values of the
+// schema that are stored as JSON (vector and dictionary content). The other values are forwarded
+// unchanged to the wrapped serializer
+pub(crate) struct ExactIntegers<'a, T: ?Sized>(pub &'a T);
+
+impl<T: Serialize + ?Sized> Serialize for ExactIntegers<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(ExactIntegerSerializer(serializer))
+    }
+}
+
+struct ExactIntegerSerializer<S>(S);
+
+macro_rules! forward_integers {
+    ($($method:ident: $ty:ty),*) => {
+        $(
+            fn $method(self, value: $ty) -> Result<S::Ok, S::Error> {
+                NumberValue::from(value).serialize(self.0)
+            }
+        )*
+    };
+}
+
+macro_rules! forward_values {
+    ($($method:ident: $ty:ty),*) => {
+        $(
+            fn $method(self, value: $ty) -> Result<S::Ok, S::Error> {
+                self.0.$method(value)
+            }
+        )*
+    };
+}
+
+impl<S: Serializer> Serializer for ExactIntegerSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = ExactIntegerSerializer<S::SerializeSeq>;
+    type SerializeTuple = ExactIntegerSerializer<S::SerializeTuple>;
+    type SerializeTupleStruct = ExactIntegerSerializer<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = ExactIntegerSerializer<S::SerializeTupleVariant>;
+    type SerializeMap = ExactIntegerSerializer<S::SerializeMap>;
+    type SerializeStruct = ExactIntegerSerializer<S::SerializeStruct>;
+    type SerializeStructVariant = ExactIntegerSerializer<S::SerializeStructVariant>;
+
+    forward_integers!(
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128
+    );
+
+    forward_values!(
+        serialize_bool: bool,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+        serialize_bytes: &[u8],
+        serialize_unit_struct: &'static str
+    );
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.0.serialize_none()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.0.serialize_some(&ExactIntegers(value))
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.0.serialize_newtype_struct(name, &ExactIntegers(value))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.0
+            .serialize_newtype_variant(name, variant_index, variant, &ExactIntegers(value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        self.0.serialize_seq(len).map(ExactIntegerSerializer)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        self.0.serialize_tuple(len).map(ExactIntegerSerializer)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        self.0
+            .serialize_tuple_struct(name, len)
+            .map(ExactIntegerSerializer)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        self.0
+            .serialize_tuple_variant(name, variant_index, variant, len)
+            .map(ExactIntegerSerializer)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        self.0.serialize_map(len).map(ExactIntegerSerializer)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        self.0
+            .serialize_struct(name, len)
+            .map(ExactIntegerSerializer)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        self.0
+            .serialize_struct_variant(name, variant_index, variant, len)
+            .map(ExactIntegerSerializer)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+// The elements of compound values are wrapped in turn
+macro_rules! impl_compound {
+    ($trait:ident, $method:ident) => {
+        impl<S: ser::$trait> ser::$trait for ExactIntegerSerializer<S> {
+            type Ok = S::Ok;
+            type Error = S::Error;
+
+            fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+                self.0.$method(&ExactIntegers(value))
+            }
+
+            fn end(self) -> Result<S::Ok, S::Error> {
+                self.0.end()
+            }
+        }
+    };
+    ($trait:ident) => {
+        impl<S: ser::$trait> ser::$trait for ExactIntegerSerializer<S> {
+            type Ok = S::Ok;
+            type Error = S::Error;
+
+            fn serialize_field<T: Serialize + ?Sized>(
+                &mut self,
+                key: &'static str,
+                value: &T,
+            ) -> Result<(), S::Error> {
+                self.0.serialize_field(key, &ExactIntegers(value))
+            }
+
+            fn skip_field(&mut self, key: &'static str) -> Result<(), S::Error> {
+                self.0.skip_field(key)
+            }
+
+            fn end(self) -> Result<S::Ok, S::Error> {
+                self.0.end()
+            }
+        }
+    };
+}
+
+impl_compound!(SerializeSeq, serialize_element);
+impl_compound!(SerializeTuple, serialize_element);
+impl_compound!(SerializeTupleStruct, serialize_field);
+impl_compound!(SerializeTupleVariant, serialize_field);
+impl_compound!(SerializeStruct);
+impl_compound!(SerializeStructVariant);
+
+impl<S: ser::SerializeMap> ser::SerializeMap for ExactIntegerSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), S::Error> {
+        self.0.serialize_key(&ExactIntegers(key))
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        self.0.serialize_value(&ExactIntegers(value))
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.0.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn ordering_across_variants() {
+        use NumberValue::*;
+
+        assert_eq!(UnsignedInteger(5), SignedInteger(5));
+        assert_eq!(SignedInteger(5), Float(5.0));
+        assert!(SignedInteger(-1) < UnsignedInteger(0));
+        assert!(UnsignedInteger(0) > SignedInteger(i128::MIN));
+        assert!(UnsignedInteger(u128::MAX) > SignedInteger(i128::MAX));
+        assert!(SignedInteger(i128::MAX) < UnsignedInteger(i128::MAX as u128 + 1));
+        assert!(Float(0.5) < UnsignedInteger(1));
+        assert!(Float(-0.5) > SignedInteger(-1));
+        assert_eq!(Float(f64::NAN).partial_cmp(&UnsignedInteger(0)), None);
+
+        // Integers are compared exactly, even where `f64` would round them to the same value
+        let max_exact = MAX_EXACT_INTEGER as u64;
+        assert!(NumberValue::from(max_exact) < NumberValue::from(max_exact + 1));
+        assert!(NumberValue::from(u64::MAX - 1) < NumberValue::from(u64::MAX));
+    }
+
+    #[test]
+    fn integers_around_max_exact_integer() {
+        let max_exact = MAX_EXACT_INTEGER as u64;
+
+        let value = serde_json::to_value(NumberValue::from(max_exact)).unwrap();
+        assert_eq!(value, json!(max_exact));
+        let value = serde_json::to_value(NumberValue::from(max_exact + 1)).unwrap();
+        assert_eq!(value, json!((max_exact + 1).to_string()));
+        let value = serde_json::to_value(NumberValue::from(-(max_exact as i128))).unwrap();
+        assert_eq!(value, json!(-(max_exact as i64)));
+        let value = serde_json::to_value(NumberValue::from(-(max_exact as i128) - 1)).unwrap();
+        assert_eq!(value, json!((-(max_exact as i128) - 1).to_string()));
+        let value = serde_json::to_value(NumberValue::from(i128::MIN)).unwrap();
+        assert_eq!(value, json!(i128::MIN.to_string()));
+        let value = serde_json::to_value(NumberValue::from(2.5)).unwrap();
+        assert_eq!(value, json!(2.5));
+
+        for value in [
+            NumberValue::from(max_exact),
+            NumberValue::from(max_exact + 1),
+            NumberValue::from(u64::MAX),
+            NumberValue::from(u128::MAX),
+            NumberValue::from(-(max_exact as i128) - 1),
+            NumberValue::from(i128::MIN),
+        ] {
+            let json = serde_json::to_string(&value).unwrap();
+            let deserialized = serde_json::from_str::<NumberValue>(&json).unwrap();
+            assert_eq!(deserialized.to_string(), value.to_string());
+        }
+
+        assert!(serde_json::from_str::<NumberValue>("\"1.5\"").is_err());
+    }
+
+    #[test]
+    fn number_type_serialization() {
+        let ty = NumberType::UnsignedInteger { bits: 16 };
+        let json = serde_json::to_value(ty).unwrap();
+        assert_eq!(json, json!({ "UnsignedInteger": { "bits": 16 } }));
+        assert_eq!(serde_json::from_value::<NumberType>(json).unwrap(), ty);
+
+        assert_eq!(
+            serde_json::from_value::<NumberType>(json!("Float")).unwrap(),
+            NumberType::Float
+        );
+        assert!(
+            serde_json::from_value::<NumberType>(json!({ "SignedInteger": { "bits": 0 } }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn legacy_number_type() {
+        assert_eq!(
+            serde_json::from_value::<NumberType>(json!("UnsignedInteger")).unwrap(),
+            NumberType::UnsignedInteger { bits: 64 }
+        );
+        assert_eq!(
+            serde_json::from_value::<NumberType>(json!("SignedInteger")).unwrap(),
+            NumberType::SignedInteger { bits: 64 }
+        );
+    }
+
+    #[test]
+    fn integer_range() {
+        let range = NumberType::UnsignedInteger { bits: 8 }
+            .integer_range()
+            .unwrap();
+        assert_eq!(range, NumberValue::from(0)..=NumberValue::from(255));
+        let range = NumberType::SignedInteger { bits: 8 }
+            .integer_range()
+            .unwrap();
+        assert_eq!(range, NumberValue::from(-128)..=NumberValue::from(127));
+        let range = NumberType::UnsignedInteger { bits: 128 }
+            .integer_range()
+            .unwrap();
+        assert_eq!(*range.end(), NumberValue::from(u128::MAX));
+        let range = NumberType::SignedInteger { bits: 128 }
+            .integer_range()
+            .unwrap();
+        assert_eq!(*range.start(), NumberValue::from(i128::MIN));
+        assert!(NumberType::Float.integer_range().is_none());
+    }
+
+    #[test]
+    fn exact_integers_in_compound_values() {
+        #[derive(Serialize)]
+        struct Wide {
+            value: u128,
+            pair: (i128, Option<u64>),
+        }
+
+        let value = [Wide {
+            value: u128::MAX,
+            pair: (i128::MIN, Some(u64::MAX)),
+        }];
+        let map = std::collections::BTreeMap::from([(u128::MAX, 1u8), (2, 3)]);
+        assert_eq!(
+            serde_json::to_value(ExactIntegers(&value)).unwrap(),
+            json!([{
+                "value": u128::MAX.to_string(),
+                "pair": [i128::MIN.to_string(), u64::MAX.to_string()],
+            }])
+        );
+        assert_eq!(
+            serde_json::to_value(ExactIntegers(&map)).unwrap(),
+            json!({ "2": 3, u128::MAX.to_string(): 1 })
+        );
+    }
+}
@@ -0,0 +1,743 @@
+use crate::{NumberType, NumberValue, NumericGuiType, SchemaDefinitions, SchemaNode};
+use serde_json::{Map, Value};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    ops::RangeInclusive,
+    time::Duration,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationErrorKind {
+    /// The value has a different JSON type. `expected` is a short description of the allowed type.
+    WrongType {
+        expected: &'static str,
+    },
+    MissingEntry,
+    UnknownEntry,
+    UnknownVariant(String),
+    NotAnInteger,
+    NegativeUnsigned,
+    /// The value is outside of the slider range or of the range of the integer type.
+    OutOfRange {
+        value: NumberValue,
+        range: RangeInclusive<NumberValue>,
+    },
+    InvertedRange,
+    LengthMismatch {
+        expected: usize,
+        found: usize,
+    },
+    DuplicateElement,
+    /// A `SchemaNode::Reference` names a type that is missing from the definitions.
+    UnknownReference(String),
+}
+
+impl Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongType { expected } => write!(f, "expected {}", expected),
+            Self::MissingEntry => write!(f, "missing entry"),
+            Self::UnknownEntry => write!(f, "unknown entry"),
+            Self::UnknownVariant(variant) => write!(f, "unknown variant \"{}\"", variant),
+            Self::NotAnInteger => write!(f, "expected an integer"),
+            Self::NegativeUnsigned => write!(f, "expected an unsigned integer"),
+            Self::OutOfRange { value, range } => write!(
+                f,
+                "{} is outside of the range {}..={}",
+                value,
+                range.start(),
+                range.end()
+            ),
+            Self::InvertedRange => write!(f, "range start is greater than range end"),
+            Self::LengthMismatch { expected, found } => {
+                write!(f, "expected {} elements, found {}", expected, found)
+            }
+            Self::DuplicateElement => write!(f, "duplicate element"),
+            Self::UnknownReference(name) => write!(f, "unknown schema reference \"{}\"", name),
+        }
+    }
+}
+
+/// Mismatch between a JSON value and a schema. `path` is a JSON pointer (RFC 6901) to the
+/// offending value, relative to the validated value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    pub path: String,
+    pub kind: ValidationErrorKind,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "(root)"
+        } else {
+            &self.path
+        };
+
+        write!(f, "{}: {}", path, self.kind)
+    }
+}
+
+impl Error for ValidationError {}
+
+// Integers of up to 64 bits are read exactly, while wider ones are parsed as floats by serde_json
+fn json_number(value: &Value) -> Option<NumberValue> {
+    if let Some(value) = value.as_u64() {
+        Some(value.into())
+    } else if let Some(value) = value.as_i64() {
+        Some(value.into())
+    } else {
+        value.as_f64().map(NumberValue::from)
+    }
+}
+
+// Floats are accepted as integers only if they are too big to be parsed as 64 bit integers, since
+// they come from wider integers
+fn is_integer(value: NumberValue) -> bool {
+    match value {
+        NumberValue::UnsignedInteger(_) | NumberValue::SignedInteger(_) => true,
+        NumberValue::Float(value) => {
+            value.fract() == 0.0 && (value >= u64::MAX as f64 || value < i64::MIN as f64)
+        }
+    }
+}
+
+fn child_path(path: &str, token: &str) -> String {
+    format!("{}/{}", path, token.replace('~', "~0").replace('/', "~1"))
+}
+
+struct Validator<'a> {
+    definitions: &'a SchemaDefinitions,
+    errors: Vec<ValidationError>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, path: &str, kind: ValidationErrorKind) {
+        self.errors.push(ValidationError {
+            path: path.to_owned(),
+            kind,
+        });
+    }
+
+    fn wrong_type(&mut self, path: &str, expected: &'static str) {
+        self.report(path, ValidationErrorKind::WrongType { expected });
+    }
+
+    fn check_range(&mut self, path: &str, value: NumberValue, range: RangeInclusive<NumberValue>) {
+        if !range.contains(&value) {
+            self.report(path, ValidationErrorKind::OutOfRange { value, range });
+        }
+    }
+
+    // Check that the value is an object with exactly the given keys, plus any of the optional keys.
+    // Returns the object if the type is correct
+    fn check_object_keys<'a>(
+        &mut self,
+        path: &str,
+        value: &'a Value,
+        keys: &[&str],
+        optional_keys: &[String],
+    ) -> Option<&'a Map<String, Value>> {
+        let object = if let Value::Object(object) = value {
+            object
+        } else {
+            self.wrong_type(path, "object");
+            return None;
+        };
+
+        for key in keys {
+            if !object.contains_key(*key) {
+                self.report(&child_path(path, key), ValidationErrorKind::MissingEntry);
+            }
+        }
+        for key in object.keys() {
+            if !keys.contains(&key.as_str()) && !optional_keys.contains(key) {
+                self.report(&child_path(path, key), ValidationErrorKind::UnknownEntry);
+            }
+        }
+
+        Some(object)
+    }
+
+    fn number(&mut self, path: &str, value: &Value, ty: NumberType, gui: &NumericGuiType) {
+        let number = if let Some(number) = json_number(value) {
+            number
+        } else {
+            self.wrong_type(path, "number");
+            return;
+        };
+
+        match ty {
+            NumberType::UnsignedInteger { .. } | NumberType::SignedInteger { .. }
+                if !is_integer(number) =>
+            {
+                self.report(path, ValidationErrorKind::NotAnInteger)
+            }
+            NumberType::UnsignedInteger { .. } if number < NumberValue::UnsignedInteger(0) => {
+                self.report(path, ValidationErrorKind::NegativeUnsigned)
+            }
+            _ => {
+                if let Some(range) = ty.integer_range() {
+                    self.check_range(path, number, range);
+                }
+            }
+        }
+
+        if let NumericGuiType::Slider { range, .. } = gui {
+            self.check_range(path, number, range.clone());
+        }
+    }
+
+    // Check a fixed length array, where each element has its own schema
+    fn sequence<'a>(
+        &mut self,
+        path: &str,
+        nodes: impl ExactSizeIterator<Item = &'a SchemaNode>,
+        value: &Value,
+    ) {
+        if let Value::Array(values) = value {
+            if values.len() != nodes.len() {
+                self.report(
+                    path,
+                    ValidationErrorKind::LengthMismatch {
+                        expected: nodes.len(),
+                        found: values.len(),
+                    },
+                );
+            }
+            for (index, (node, value)) in nodes.zip(values).enumerate() {
+                self.node(&child_path(path, &index.to_string()), node, value);
+            }
+        } else {
+            self.wrong_type(path, "array");
+        }
+    }
+
+    // Map keys are always serialized as strings. Numeric keys are parsed before being checked
+    fn map_key(&mut self, path: &str, node: &SchemaNode, key: &str) {
+        let key = if let SchemaNode::Number { .. } = node {
+            match serde_json::from_str::<Value>(key) {
+                Ok(key) if key.is_number() => key,
+                _ => {
+                    self.wrong_type(path, "numeric key");
+                    return;
+                }
+            }
+        } else {
+            Value::String(key.to_owned())
+        };
+
+        self.node(path, node, &key);
+    }
+
+    fn node(&mut self, path: &str, node: &SchemaNode, value: &Value) {
+        match node {
+            SchemaNode::Section {
+                entries, skipped, ..
+            } => {
+                let names = entries
+                    .iter()
+                    .map(|entry| entry.name.as_str())
+                    .collect::<Vec<_>>();
+                if let Some(object) = self.check_object_keys(path, value, &names, skipped) {
+                    for entry in entries {
+                        if let Some(value) = object.get(&entry.name) {
+                            self.node(&child_path(path, &entry.name), &entry.content, value);
+                        }
+                    }
+                }
+            }
+            SchemaNode::Choice {
+                variants, skipped, ..
+            } => {
+                let (name, content) = match value {
+                    Value::String(name) => (name, None),
+                    Value::Object(object) if object.len() == 1 => {
+                        let (name, content) = object.iter().next().unwrap();
+                        (name, Some(content))
+                    }
+                    _ => {
+                        self.wrong_type(path, "variant name or object with a single key");
+                        return;
+                    }
+                };
+
+                let variant =
+                    if let Some(variant) = variants.iter().find(|variant| variant.name == *name) {
+                        variant
+                    } else {
+                        // The content of skipped variants is not described
+                        if !skipped.contains(name) {
+                            self.report(path, ValidationErrorKind::UnknownVariant(name.clone()));
+                        }
+                        return;
+                    };
+
+                match (&variant.content, content) {
+                    (Some(node), Some(content)) => {
+                        self.node(&child_path(path, name), node, content)
+                    }
+                    (None, None) => (),
+                    (Some(_), None) => self.wrong_type(path, "object with a single key"),
+                    (None, Some(_)) => self.wrong_type(path, "variant name"),
+                }
+            }
+            SchemaNode::Optional { content, .. } => {
+                if !value.is_null() {
+                    self.node(path, content, value);
+                }
+            }
+            SchemaNode::Switch { content, .. } => match value {
+                Value::String(name) if name == "Disabled" => (),
+                Value::Object(object) if object.len() == 1 && object.contains_key("Enabled") => {
+                    self.node(&child_path(path, "Enabled"), content, &object["Enabled"])
+                }
+                _ => self.wrong_type(path, "\"Disabled\" or object with key \"Enabled\""),
+            },
+            SchemaNode::Boolean { .. } => {
+                if !value.is_boolean() {
+                    self.wrong_type(path, "boolean");
+                }
+            }
+            SchemaNode::Number { ty, gui, .. } => self.number(path, value, *ty, gui),
+            SchemaNode::Range { ty, gui, .. } => {
+                if let Some(object) = self.check_object_keys(path, value, &["start", "end"], &[]) {
+                    for key in ["start", "end"] {
+                        if let Some(value) = object.get(key) {
+                            self.number(&child_path(path, key), value, *ty, gui);
+                        }
+                    }
+
+                    let start = object.get("start").and_then(json_number);
+                    let end = object.get("end").and_then(json_number);
+                    if let (Some(start), Some(end)) = (start, end) {
+                        if start > end {
+                            self.report(path, ValidationErrorKind::InvertedRange);
+                        }
+                    }
+                }
+            }
+            SchemaNode::Percentage {
+                range_normalized, ..
+            } => {
+                if let Some(value) = value.as_f64() {
+                    if let Some(range) = range_normalized {
+                        self.check_range(
+                            path,
+                            value.into(),
+                            (*range.start()).into()..=(*range.end()).into(),
+                        );
+                    }
+                } else {
+                    self.wrong_type(path, "number");
+                }
+            }
+            SchemaNode::Duration { unit, gui, .. } => {
+                if let Some(object) = self.check_object_keys(path, value, &["secs", "nanos"], &[]) {
+                    let secs = object.get("secs").map(Value::as_u64);
+                    let nanos = object
+                        .get("nanos")
+                        .map(|nanos| nanos.as_u64().filter(|nanos| *nanos < 1_000_000_000));
+
+                    if let Some(None) = secs {
+                        self.wrong_type(&child_path(path, "secs"), "unsigned integer");
+                    }
+                    if let Some(None) = nanos {
+                        self.wrong_type(&child_path(path, "nanos"), "integer below 1000000000");
+                    }
+
+                    if let (Some(Some(secs)), Some(Some(nanos))) = (secs, nanos) {
+                        if let NumericGuiType::Slider { range, .. } = gui {
+                            let count = unit.count(Duration::new(secs, nanos as u32));
+                            self.check_range(path, count.into(), range.clone());
+                        }
+                    }
+                }
+            }
+            SchemaNode::Text { .. } => {
+                if !value.is_string() {
+                    self.wrong_type(path, "string");
+                }
+            }
+            SchemaNode::Array(nodes) => self.sequence(path, nodes.iter(), value),
+            SchemaNode::Tuple { entries, .. } => {
+                self.sequence(path, entries.iter().map(|entry| &entry.content), value)
+            }
+            SchemaNode::Vector {
+                default_element, ..
+            } => {
+                if let Value::Array(values) = value {
+                    for (index, value) in values.iter().enumerate() {
+                        self.node(
+                            &child_path(path, &index.to_string()),
+                            default_element,
+                            value,
+                        );
+                    }
+                } else {
+                    self.wrong_type(path, "array");
+                }
+            }
+            SchemaNode::Set {
+                default_element, ..
+            } => {
+                if let Value::Array(values) = value {
+                    for (index, value) in values.iter().enumerate() {
+                        let element_path = child_path(path, &index.to_string());
+                        self.node(&element_path, default_element, value);
+                        if values[..index].contains(value) {
+                            self.report(&element_path, ValidationErrorKind::DuplicateElement);
+                        }
+                    }
+                } else {
+                    self.wrong_type(path, "array");
+                }
+            }
+            SchemaNode::Dictionary {
+                key: key_node,
+                default_value,
+                map: true,
+                ..
+            } => {
+                if let Value::Object(entries) = value {
+                    for (key, value) in entries {
+                        let entry_path = child_path(path, key);
+                        if let Some(key_node) = key_node {
+                            self.map_key(&entry_path, key_node, key);
+                        }
+                        self.node(&entry_path, default_value, value);
+                    }
+                } else {
+                    self.wrong_type(path, "object");
+                }
+            }
+            SchemaNode::Dictionary {
+                key: key_node,
+                default_value,
+                ..
+            } => {
+                if let Value::Array(entries) = value {
+                    for (index, entry) in entries.iter().enumerate() {
+                        let entry_path = child_path(path, &index.to_string());
+                        match entry {
+                            Value::Array(pair) if pair.len() == 2 => {
+                                let key_path = child_path(&entry_path, "0");
+                                if let Some(key_node) = key_node {
+                                    self.node(&key_path, key_node, &pair[0]);
+                                } else if !pair[0].is_string() {
+                                    self.wrong_type(&key_path, "string");
+                                }
+                                self.node(&child_path(&entry_path, "1"), default_value, &pair[1]);
+                            }
+                            _ => self.wrong_type(&entry_path, "[key, value] pair"),
+                        }
+                    }
+                } else {
+                    self.wrong_type(path, "array");
+                }
+            }
+            SchemaNode::Reference(name) => {
+                if let Some(node) = self.definitions.get(name) {
+                    self.node(path, node, value);
+                } else {
+                    self.report(path, ValidationErrorKind::UnknownReference(name.clone()));
+                }
+            }
+        }
+    }
+}
+
+impl SchemaNode {
+    /// Check that `value` is a valid serialized instance of the settings described by this schema
+    /// (not of the `*Default` representation). All mismatches are returned, not only the first one.
+    /// Schemas containing `SchemaNode::Reference` nodes must be validated with
+    /// `validate_with_definitions()`.
+    pub fn validate(&self, value: &Value) -> Result<(), Vec<ValidationError>> {
+        self.validate_with_definitions(&SchemaDefinitions::new(), value)
+    }
+
+    /// Like `validate()`, resolving `SchemaNode::Reference` nodes in `definitions` (usually
+    /// obtained with `SettingsSchema::schema_definitions()`).
+    pub fn validate_with_definitions(
+        &self,
+        definitions: &SchemaDefinitions,
+        value: &Value,
+    ) -> Result<(), Vec<ValidationError>> {
+        let mut validator = Validator {
+            definitions,
+            errors: vec![],
+        };
+        validator.node("", self, value);
+
+        if validator.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(validator.errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SchemaEntry;
+    use serde_json::json;
+
+    fn entry<T>(name: &str, content: T) -> SchemaEntry<T> {
+        SchemaEntry {
+            name: name.into(),
+            strings: Default::default(),
+            flags: Default::default(),
+            content,
+        }
+    }
+
+    fn number(ty: NumberType, gui: NumericGuiType) -> SchemaNode {
+        SchemaNode::Number {
+            default: 0.into(),
+            ty,
+            gui,
+            suffix: None,
+        }
+    }
+
+    fn u8_node() -> SchemaNode {
+        number(
+            NumberType::UnsignedInteger { bits: 8 },
+            NumericGuiType::TextBox,
+        )
+    }
+
+    fn section() -> SchemaNode {
+        SchemaNode::Section {
+            entries: vec![
+                entry("a", u8_node()),
+                entry("b", SchemaNode::Text { default: "".into() }),
+            ],
+            gui_collapsible: false,
+            strings: Default::default(),
+            skipped: vec!["c".into()],
+        }
+    }
+
+    fn choice() -> SchemaNode {
+        SchemaNode::Choice {
+            default: "A".into(),
+            variants: vec![entry("A", None), entry("B", Some(u8_node()))],
+            gui: None,
+            strings: Default::default(),
+            skipped: vec!["C".into()],
+        }
+    }
+
+    fn errors(node: &SchemaNode, value: Value) -> Vec<(String, ValidationErrorKind)> {
+        match node.validate(&value) {
+            Ok(()) => vec![],
+            Err(errors) => errors
+                .into_iter()
+                .map(|error| (error.path, error.kind))
+                .collect(),
+        }
+    }
+
+    fn error(path: &str, kind: ValidationErrorKind) -> Vec<(String, ValidationErrorKind)> {
+        vec![(path.into(), kind)]
+    }
+
+    #[test]
+    fn valid_values() {
+        assert!(section().validate(&json!({ "a": 1, "b": "" })).is_ok());
+        assert!(section()
+            .validate(&json!({ "a": 1, "b": "", "c": [] }))
+            .is_ok());
+        assert!(choice().validate(&json!("A")).is_ok());
+        assert!(choice().validate(&json!({ "B": 255 })).is_ok());
+        assert!(choice().validate(&json!({ "C": null })).is_ok());
+    }
+
+    #[test]
+    fn wrong_type() {
+        assert_eq!(
+            errors(&section(), json!({ "a": "1", "b": "" })),
+            error("/a", ValidationErrorKind::WrongType { expected: "number" })
+        );
+        assert_eq!(
+            errors(&choice(), json!({ "A": 1 })),
+            error(
+                "",
+                ValidationErrorKind::WrongType {
+                    expected: "variant name"
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn missing_and_unknown_entries() {
+        assert_eq!(
+            errors(&section(), json!({ "a": 1 })),
+            error("/b", ValidationErrorKind::MissingEntry)
+        );
+        assert_eq!(
+            errors(&section(), json!({ "a": 1, "b": "", "d/e": 0 })),
+            error("/d~1e", ValidationErrorKind::UnknownEntry)
+        );
+    }
+
+    #[test]
+    fn unknown_variant() {
+        assert_eq!(
+            errors(&choice(), json!("D")),
+            error("", ValidationErrorKind::UnknownVariant("D".into()))
+        );
+    }
+
+    #[test]
+    fn integers() {
+        let i8_node = number(
+            NumberType::SignedInteger { bits: 8 },
+            NumericGuiType::TextBox,
+        );
+
+        assert_eq!(
+            errors(&u8_node(), json!(1.5)),
+            error("", ValidationErrorKind::NotAnInteger)
+        );
+        // serde_json does not deserialize integers from floats
+        assert_eq!(
+            errors(&u8_node(), json!(1.0)),
+            error("", ValidationErrorKind::NotAnInteger)
+        );
+        assert_eq!(
+            errors(&u8_node(), json!(-1)),
+            error("", ValidationErrorKind::NegativeUnsigned)
+        );
+        assert_eq!(
+            errors(&u8_node(), json!(256)),
+            error(
+                "",
+                ValidationErrorKind::OutOfRange {
+                    value: 256.into(),
+                    range: 0.into()..=255.into(),
+                }
+            )
+        );
+        assert!(i8_node.validate(&json!(-128)).is_ok());
+        assert_eq!(
+            errors(&i8_node, json!(-129)),
+            error(
+                "",
+                ValidationErrorKind::OutOfRange {
+                    value: (-129).into(),
+                    range: (-128).into()..=127.into(),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn wide_integers() {
+        let u64_node = number(
+            NumberType::UnsignedInteger { bits: 64 },
+            NumericGuiType::TextBox,
+        );
+        let i128_node = number(
+            NumberType::SignedInteger { bits: 128 },
+            NumericGuiType::TextBox,
+        );
+
+        assert!(u64_node.validate(&json!(u64::MAX)).is_ok());
+        assert!(u64_node.validate(&json!(9_007_199_254_740_993_u64)).is_ok());
+        assert!(i128_node.validate(&json!(i64::MIN)).is_ok());
+    }
+
+    #[test]
+    fn slider_range() {
+        let node = number(
+            NumberType::Float,
+            NumericGuiType::Slider {
+                range: 0.0.into()..=1.0.into(),
+                step: None,
+                logarithmic: false,
+            },
+        );
+
+        assert!(node.validate(&json!(0.5)).is_ok());
+        assert_eq!(
+            errors(&node, json!(2)),
+            error(
+                "",
+                ValidationErrorKind::OutOfRange {
+                    value: 2.into(),
+                    range: 0.0.into()..=1.0.into(),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn inverted_range() {
+        let node = SchemaNode::Range {
+            default: [0.into(), 0.into()],
+            ty: NumberType::UnsignedInteger { bits: 8 },
+            gui: NumericGuiType::TextBox,
+            suffix: None,
+        };
+
+        assert!(node.validate(&json!({ "start": 1, "end": 1 })).is_ok());
+        assert_eq!(
+            errors(&node, json!({ "start": 2, "end": 1 })),
+            error("", ValidationErrorKind::InvertedRange)
+        );
+    }
+
+    #[test]
+    fn length_mismatch() {
+        let node = SchemaNode::Array(vec![u8_node(), u8_node()]);
+
+        assert_eq!(
+            errors(&node, json!([1, 2, 3])),
+            error(
+                "",
+                ValidationErrorKind::LengthMismatch {
+                    expected: 2,
+                    found: 3,
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn duplicate_element() {
+        let node = SchemaNode::Set {
+            default_element: Box::new(u8_node()),
+            options: None,
+            default: vec![],
+        };
+
+        assert!(node.validate(&json!([1, 2])).is_ok());
+        assert_eq!(
+            errors(&node, json!([1, 2, 1])),
+            error("/2", ValidationErrorKind::DuplicateElement)
+        );
+    }
+
+    #[test]
+    fn references() {
+        let node = SchemaNode::Vector {
+            default_element: Box::new(SchemaNode::Reference("Node".into())),
+            default: vec![],
+        };
+        let mut definitions = SchemaDefinitions::new();
+        definitions.insert("Node".into(), section());
+
+        assert!(node
+            .validate_with_definitions(&definitions, &json!([{ "a": 1, "b": "" }]))
+            .is_ok());
+        assert_eq!(
+            errors(&node, json!([{ "a": 1, "b": "" }])),
+            error("/0", ValidationErrorKind::UnknownReference("Node".into()))
+        );
+    }
+}
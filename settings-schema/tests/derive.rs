@@ -0,0 +1,529 @@
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use settings_schema::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+fn schema_json<T: SettingsSchema>() -> Value
+where
+    T::Default: Default,
+{
+    serde_json::to_value(T::schema(T::Default::default())).unwrap()
+}
+
+fn entry_names(section: &Value) -> Vec<&str> {
+    section["Section"]["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["name"].as_str().unwrap())
+        .collect()
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Renamed {
+    frame_rate: u32,
+    #[serde(rename = "bitrate")]
+    bitrate_mbps: u32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case", rename_all_fields = "kebab-case")]
+enum RenamedEnum {
+    HighQuality,
+    #[serde(rename = "fast")]
+    LowLatency,
+    Custom {
+        max_bitrate: u32,
+    },
+}
+
+#[test]
+fn rename() {
+    let schema = schema_json::<Renamed>();
+    assert_eq!(entry_names(&schema), ["frameRate", "bitrate"]);
+
+    let schema = schema_json::<RenamedEnum>();
+    let variants = &schema["Choice"]["variants"];
+    assert_eq!(variants[0]["name"], "high_quality");
+    assert_eq!(variants[1]["name"], "fast");
+    assert_eq!(variants[2]["name"], "custom");
+    assert_eq!(
+        variants[2]["content"]["Section"]["entries"][0]["name"],
+        "max-bitrate"
+    );
+
+    // The default representation uses the same names
+    let default = serde_json::to_value(RenamedDefault::default()).unwrap();
+    assert_eq!(default, json!({ "frameRate": 0, "bitrate": 0 }));
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct Inner {
+    a: bool,
+    b: bool,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct Flattened {
+    #[serde(flatten)]
+    inner: Inner,
+    c: bool,
+}
+
+#[test]
+fn flatten() {
+    let schema = schema_json::<Flattened>();
+    assert_eq!(entry_names(&schema), ["a", "b", "c"]);
+
+    let default = serde_json::to_value(FlattenedDefault::default()).unwrap();
+    assert_eq!(default, json!({ "a": false, "b": false, "c": false }));
+
+    let value = Flattened::from(FlattenedDefault::default());
+    assert!(!value.inner.a && !value.c);
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct Skipped {
+    #[serde(skip)]
+    not_serialized: Option<std::rc::Rc<()>>,
+    #[schema(skip)]
+    not_described: u8,
+    kept: bool,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+enum SkippedEnum {
+    Kept,
+    #[schema(skip)]
+    NotDescribed(u8),
+}
+
+#[test]
+fn skip() {
+    let schema = schema_json::<Skipped>();
+    assert_eq!(entry_names(&schema), ["kept"]);
+    assert_eq!(schema["Section"]["skipped"], json!(["not_described"]));
+
+    let schema = Skipped::schema(Default::default());
+    assert!(schema
+        .validate(&json!({ "not_described": 3, "kept": true }))
+        .is_ok());
+    assert!(schema
+        .validate(&json!({ "other": 3, "kept": true }))
+        .is_err());
+
+    let value = Skipped::from(SkippedDefault { kept: true });
+    assert_eq!(value.not_described, 0);
+
+    let schema = schema_json::<SkippedEnum>();
+    assert_eq!(schema["Choice"]["variants"].as_array().unwrap().len(), 1);
+    assert_eq!(schema["Choice"]["skipped"], json!(["NotDescribed"]));
+
+    // A skipped variant keeps the previous default variant
+    let default = SkippedEnumDefault::default();
+    let default = SkippedEnum::default_from_value(default, &SkippedEnum::NotDescribed(1));
+    assert!(matches!(SkippedEnum::from(default), SkippedEnum::Kept));
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct PerEye<T> {
+    left: T,
+    right: T,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct Generic {
+    #[schema(default = PerEye { left: 1.0, right: 2.0 })]
+    fov: PerEye<f32>,
+    names: PerEye<String>,
+}
+
+#[test]
+fn generics() {
+    let schema = schema_json::<Generic>();
+    let fov = &schema["Section"]["entries"][0]["content"];
+    assert_eq!(entry_names(fov), ["left", "right"]);
+    assert_eq!(
+        fov["Section"]["entries"][1]["content"]["Number"]["default"],
+        2.0
+    );
+    let names = &schema["Section"]["entries"][1]["content"];
+    assert_eq!(
+        names["Section"]["entries"][0]["content"],
+        json!({ "Text": { "default": "" } })
+    );
+
+    let default: PerEyeDefault<u8> = PerEyeDefault { left: 1, right: 2 };
+    let value = PerEye::from(default.clone());
+    assert_eq!((value.left, value.right), (1, 2));
+    let default = PerEyeDefault::from_value(default, &PerEye { left: 3, right: 4 });
+    assert_eq!((default.left, default.right), (3, 4));
+}
+
+mod other {
+    #[derive(serde::Serialize, serde::Deserialize, Clone)]
+    pub struct Color {
+        pub red: u8,
+        pub green: u8,
+        pub blue: u8,
+    }
+
+    #[derive(settings_schema::SettingsSchema, serde::Serialize, serde::Deserialize, Clone)]
+    pub struct Config {
+        pub enabled: bool,
+    }
+
+    // Has the name of a built-in type
+    #[derive(settings_schema::SettingsSchema, serde::Serialize, serde::Deserialize, Clone)]
+    pub struct Switch<T> {
+        pub value: T,
+    }
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(remote = "other::Color")]
+struct ColorMirror {
+    #[schema(default = 255)]
+    red: u8,
+    green: u8,
+    blue: u8,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct Remote {
+    #[schema(with = "ColorMirror")]
+    color: other::Color,
+    #[schema(with = "ColorMirror")]
+    palette: Vec<other::Color>,
+}
+
+#[test]
+fn remote() {
+    let schema = schema_json::<Remote>();
+    let color = &schema["Section"]["entries"][0]["content"];
+    assert_eq!(entry_names(color), ["red", "green", "blue"]);
+    assert_eq!(
+        color["Section"]["entries"][0]["content"]["Number"]["default"],
+        255
+    );
+    let palette = &schema["Section"]["entries"][1]["content"]["Vector"];
+    assert_eq!(
+        entry_names(&palette["default_element"]),
+        ["red", "green", "blue"]
+    );
+
+    let color = <ColorMirror as RemoteSettingsSchema<_>>::from_default(ColorMirrorDefault {
+        red: 1,
+        green: 2,
+        blue: 3,
+    });
+    assert_eq!((color.red, color.green, color.blue), (1, 2, 3));
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Codec {
+    H264,
+    Hevc,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct Collections {
+    #[schema(default = [("a".into(), 1)].into())]
+    hash_map: HashMap<String, u8>,
+    #[schema(default = [(2, true)].into())]
+    btree_map: BTreeMap<u8, bool>,
+    pairs: Vec<(String, bool)>,
+    hash_set: HashSet<u8>,
+    btree_set: BTreeSet<Codec>,
+    #[schema(multi_select, default = vec![Codec::Hevc])]
+    multi_select: Vec<Codec>,
+}
+
+#[test]
+fn maps_and_sets() {
+    let schema = schema_json::<Collections>();
+    let content = |index: usize| &schema["Section"]["entries"][index]["content"];
+
+    let hash_map = &content(0)["Dictionary"];
+    assert_eq!(hash_map["map"], true);
+    assert_eq!(hash_map["ordered"], false);
+    assert_eq!(hash_map["default_key"], "");
+    assert!(hash_map.get("key").is_none());
+    assert_eq!(hash_map["default"], json!([["a", 1]]));
+
+    let btree_map = &content(1)["Dictionary"];
+    assert_eq!(btree_map["map"], true);
+    assert_eq!(
+        btree_map["key"]["Number"]["ty"],
+        json!({ "UnsignedInteger": { "bits": 8 } })
+    );
+    // Typed keys are stored in serialized form
+    assert_eq!(btree_map["default_key"], "0");
+    assert_eq!(btree_map["default"], json!([["2", true]]));
+
+    let pairs = &content(2)["Dictionary"];
+    assert_eq!(pairs["map"], false);
+    assert_eq!(pairs["ordered"], true);
+
+    assert!(content(3)["Set"]["options"].is_null());
+    let options = content(4)["Set"]["options"].as_array().unwrap();
+    assert_eq!(options.len(), 2);
+    assert_eq!(options[1]["name"], "Hevc");
+    // Like for vectors, the default content is made of default representations
+    assert_eq!(content(5)["Set"]["default"], json!([{ "variant": "Hevc" }]));
+
+    let schema = Collections::schema(Default::default());
+    assert!(schema
+        .validate(&json!({
+            "hash_map": { "a": 1 },
+            "btree_map": { "2": true },
+            "pairs": [["a", true]],
+            "hash_set": [1, 2],
+            "btree_set": ["H264"],
+            "multi_select": ["H264", "Hevc"],
+        }))
+        .is_ok());
+    assert!(schema
+        .validate(&json!({
+            "hash_map": {},
+            "btree_map": { "x": true },
+            "pairs": [],
+            "hash_set": [1, 1],
+            "btree_set": [],
+            "multi_select": [],
+        }))
+        .is_err());
+}
+
+#[test]
+fn dictionary_format() {
+    let schema = schema_json::<Collections>();
+    let pairs = &schema["Section"]["entries"][2]["content"];
+
+    // Dictionaries with string keys have the same fields as before the introduction of typed keys
+    assert_eq!(
+        *pairs,
+        json!({
+            "Dictionary": {
+                "default_key": "",
+                "default_value": { "Boolean": { "default": false } },
+                "default": [],
+                "ordered": true,
+                "map": false,
+            }
+        })
+    );
+
+    let legacy = json!({
+        "Dictionary": {
+            "default_key": "key",
+            "default_value": { "Boolean": { "default": false } },
+            "default": [["a", true]],
+        }
+    });
+    let node = serde_json::from_value::<SchemaNode>(legacy).unwrap();
+    if let SchemaNode::Dictionary {
+        default_key,
+        key,
+        default,
+        ordered,
+        map,
+        ..
+    } = node
+    {
+        assert_eq!(default_key, "key");
+        assert!(key.is_none());
+        assert_eq!(default, [("a".into(), json!(true))]);
+        assert!(ordered && !map);
+    } else {
+        panic!("expected a dictionary");
+    }
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct WideIntegers {
+    #[schema(default = vec![u128::MAX])]
+    vector: Vec<u128>,
+    #[schema(default = [i128::MIN].into())]
+    set: BTreeSet<i128>,
+    #[schema(default = [(u128::MAX, u64::MAX)].into())]
+    map: BTreeMap<u128, u64>,
+    #[schema(default = PerEye { left: vec![u128::MAX], right: vec![] })]
+    generic: PerEye<Vec<u128>>,
+}
+
+#[test]
+fn wide_integers() {
+    let schema = schema_json::<WideIntegers>();
+    let content = |index: usize| &schema["Section"]["entries"][index]["content"];
+    let max = json!(u128::MAX.to_string());
+
+    assert_eq!(content(0)["Vector"]["default"], json!([max]));
+    assert_eq!(content(1)["Set"]["default"], json!([i128::MIN.to_string()]));
+    let map = &content(2)["Dictionary"];
+    assert_eq!(map["default_key"], "0");
+    assert_eq!(map["default"], json!([[max, u64::MAX.to_string()]]));
+    assert_eq!(
+        content(3)["Section"]["entries"][0]["content"]["Vector"]["default"],
+        json!([max])
+    );
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct Node {
+    value: u8,
+    children: Vec<Node>,
+    next: Option<Box<Self>>,
+}
+
+#[test]
+fn recursion() {
+    let name = Node::schema_name().unwrap();
+    assert_eq!(name, "derive::Node");
+
+    let schema = schema_json::<Node>();
+    let children = &schema["Section"]["entries"][1]["content"]["Vector"];
+    assert_eq!(children["default_element"], json!({ "Reference": name }));
+    let next = &schema["Section"]["entries"][2]["content"]["Optional"];
+    assert_eq!(next["content"], json!({ "Reference": name }));
+
+    let definitions = Node::schema_definitions();
+    assert_eq!(definitions.keys().collect::<Vec<_>>(), [&name]);
+
+    let schema = Node::schema(Default::default());
+    let value = json!({
+        "value": 1,
+        "children": [{ "value": 2, "children": [], "next": null }],
+        "next": { "value": 3, "children": [], "next": null },
+    });
+    assert!(schema
+        .validate_with_definitions(&definitions, &value)
+        .is_ok());
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(reference)]
+struct Shared {
+    #[schema(default = 5)]
+    level: u8,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct Users {
+    first: Shared,
+    all: Vec<Shared>,
+    boxed: Option<Box<Shared>>,
+}
+
+#[test]
+fn referenced_type() {
+    let name = Shared::schema_name().unwrap();
+    let reference = json!({ "Reference": name });
+
+    let schema = schema_json::<Users>();
+    let content = |index: usize| &schema["Section"]["entries"][index]["content"];
+    assert_eq!(*content(0), reference);
+    assert_eq!(content(1)["Vector"]["default_element"], reference);
+    assert_eq!(content(2)["Optional"]["content"], reference);
+
+    let definitions = Users::schema_definitions();
+    assert_eq!(definitions.keys().collect::<Vec<_>>(), [&name]);
+    let definition = serde_json::to_value(&definitions[&name]).unwrap();
+    assert_eq!(
+        definition["Section"]["entries"][0]["content"]["Number"]["default"],
+        5
+    );
+    assert_eq!(
+        serde_json::to_value(Shared::schema_definitions()).unwrap(),
+        json!({ &name: definition })
+    );
+
+    // Defaults that differ from the ones of the definition are inlined
+    let schema = serde_json::to_value(Shared::schema(SharedDefault { level: 1 })).unwrap();
+    assert_eq!(
+        schema["Section"]["entries"][0]["content"]["Number"]["default"],
+        1
+    );
+
+    let value = json!({ "first": { "level": 1 }, "all": [{ "level": 2 }], "boxed": null });
+    assert!(Users::schema(Default::default())
+        .validate_with_definitions(&definitions, &value)
+        .is_ok());
+}
+
+// Types that share the name of a field type are not recursive when the field type is qualified
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(unit = "ms")]
+struct Duration(std::time::Duration);
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct Config {
+    inner: other::Config,
+}
+
+#[test]
+fn qualified_namesakes() {
+    let schema = schema_json::<Duration>();
+    assert_eq!(schema["Duration"]["unit"], "Milliseconds");
+    assert!(Duration::schema_definitions().is_empty());
+
+    let schema = schema_json::<Config>();
+    let inner = &schema["Section"]["entries"][0]["content"];
+    assert_eq!(entry_names(inner), ["enabled"]);
+    assert!(Config::schema_definitions().is_empty());
+}
+
+mod custom_switch {
+    use super::other::Switch;
+    use settings_schema::*;
+
+    #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+    pub struct CustomSwitch {
+        #[schema(as = "custom")]
+        pub switch: Switch<u8>,
+    }
+}
+
+#[test]
+fn custom_wrapper_name() {
+    let schema = schema_json::<custom_switch::CustomSwitch>();
+    let switch = &schema["Section"]["entries"][0]["content"];
+    assert_eq!(entry_names(switch), ["value"]);
+
+    let value = custom_switch::CustomSwitch::from(custom_switch::CustomSwitchDefault::default());
+    assert_eq!(value.switch.value, 0);
+}
+
+#[cfg(feature = "indexmap")]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+struct IndexMaps {
+    #[schema(default = [("b".into(), 1), ("a".into(), 2)].into())]
+    field: indexmap::IndexMap<String, u8>,
+    generic: PerEye<indexmap::IndexMap<String, u8>>,
+    tuple: (indexmap::IndexMap<u8, bool>, bool),
+}
+
+#[cfg(feature = "indexmap")]
+#[test]
+fn index_maps() {
+    let schema = schema_json::<IndexMaps>();
+    let content = |index: usize| &schema["Section"]["entries"][index]["content"];
+
+    let field = &content(0)["Dictionary"];
+    assert_eq!(field["map"], true);
+    assert_eq!(field["ordered"], true);
+    assert_eq!(field["default"], json!([["b", 1], ["a", 2]]));
+
+    let generic = &content(1)["Section"]["entries"][0]["content"]["Dictionary"];
+    assert_eq!(generic["ordered"], true);
+    assert_eq!(generic["default_key"], "");
+    assert!(generic.get("key").is_none());
+
+    let tuple = &content(2)["Tuple"]["entries"][0]["content"]["Dictionary"];
+    assert_eq!(tuple["ordered"], true);
+
+    let value = IndexMaps::from(IndexMapsDefault::default());
+    assert_eq!(value.field.keys().collect::<Vec<_>>(), ["b", "a"]);
+}